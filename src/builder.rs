@@ -0,0 +1,115 @@
+//! Building configs in code, for embedding the simulator in other programs and their tests
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use crate::{
+    AsConfig, ConfigWarnings, DataPlaneConfig, EndhostApiConfig, LinkConfig, PocketScionConfig,
+    RouterConfig, SnapConfig, build_runtime_state,
+};
+
+/// Builds a [`PocketScionConfig`] entry by entry. The entries take the same values as in a config
+/// file, and the config is only validated by [`ConfigBuilder::build`].
+#[derive(Debug, Default)]
+pub struct ConfigBuilder {
+    config: PocketScionConfig,
+}
+
+impl ConfigBuilder {
+    /// A builder for a config without any ASes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder that extends an existing config, for example one read with
+    /// [`load_configs`](crate::load_configs)
+    pub fn from_config(config: PocketScionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Add an AS to the topology
+    pub fn with_as(mut self, isd_as: &str, is_core: bool) -> Self {
+        self.config.topology.ases.push(AsConfig {
+            isd_as: isd_as.to_string(),
+            is_core,
+            default_mtu: None,
+            default_bandwidth_bps: None,
+        });
+        self
+    }
+
+    /// Add a link in the string format of the config, like `1-1#1 parent_of 1-2#1`
+    pub fn with_link(mut self, link: &str) -> Self {
+        self.config
+            .topology
+            .links
+            .push(LinkConfig::Plain(link.to_string()));
+        self
+    }
+
+    /// Add a SNAP with its control plane listening on `listening_addr` and its data plane in
+    /// `isd_as` listening on `data_plane_addr`
+    pub fn with_snap(
+        mut self,
+        listening_addr: SocketAddr,
+        isd_as: &str,
+        data_plane_addr: SocketAddr,
+    ) -> Self {
+        self.config.snaps.get_or_insert_default().push(SnapConfig {
+            listening_addr,
+            data_plane: DataPlaneConfig {
+                isd_as: isd_as.to_string(),
+                listening_addr: data_plane_addr,
+            },
+            enabled: true,
+        });
+        self
+    }
+
+    /// Add an endhost API serving the ASes `isds`
+    pub fn with_endhost_api(mut self, isds: &[&str], listening_addr: SocketAddr) -> Self {
+        self.config
+            .endhost_apis
+            .get_or_insert_default()
+            .push(EndhostApiConfig {
+                isds: isds.iter().map(|isd_as| isd_as.to_string()).collect(),
+                listening_addr,
+                enabled: true,
+            });
+        self
+    }
+
+    /// Add a router of `isd_as` with the given interface IDs
+    pub fn with_router(
+        mut self,
+        isd_as: &str,
+        interfaces: &[u16],
+        listening_addr: SocketAddr,
+    ) -> Self {
+        self.config
+            .routers
+            .get_or_insert_default()
+            .push(RouterConfig {
+                isd_as: isd_as.to_string(),
+                interfaces: interfaces.to_vec(),
+                listening_addr,
+                snap_data_plane_excludes: Vec::new(),
+                snap_data_plane_interfaces: BTreeMap::new(),
+                enabled: true,
+            });
+        self
+    }
+
+    /// Set the listen address of the management API, which is an ephemeral port on localhost
+    /// otherwise
+    pub fn with_management_listen_addr(mut self, addr: SocketAddr) -> Self {
+        self.config.management_listen_addr = Some(addr);
+        self
+    }
+
+    /// Check the config like `--validate` does and return it
+    pub fn build(self) -> anyhow::Result<PocketScionConfig> {
+        build_runtime_state(&self.config, ConfigWarnings::Log)?;
+        Ok(self.config)
+    }
+}
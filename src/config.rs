@@ -0,0 +1,1382 @@
+//! The config file format, and reading and merging config files
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use ipnet::IpNet;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+use crate::schema::{IpNetwork, IsdAs, Link, SocketAddress};
+
+/// Supported config file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            _ => Err(format!(
+                "unknown config format '{format}', expected json or yaml"
+            )),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Determine the format from the extension of `path`, if it is a known one
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Determine the format from an HTTP `Content-Type`, if it is a known one
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/json" => Some(ConfigFormat::Json),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                Some(ConfigFormat::Yaml)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How fields that are not part of the config format are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownFields {
+    /// An unknown field is an error, so a misspelled field is not silently ignored
+    #[default]
+    Deny,
+    /// Unknown fields are removed with a warning, for configs written for newer versions
+    Warn,
+}
+
+/// How configs are read by [`load_configs`]
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Format of the configs, which is otherwise taken from the file extension. The fragments
+    /// they include are always parsed by their extension.
+    pub format: Option<ConfigFormat>,
+    /// How fields that are not part of the config format are treated
+    pub unknown_fields: UnknownFields,
+    /// Values of `${NAME}` references, which take precedence over environment variables
+    pub vars: BTreeMap<String, String>,
+}
+
+/// Read the configs at `paths` and merge them in order. A directory stands for the JSON and YAML
+/// files in it, in lexical order.
+pub fn load_configs(paths: &[String], options: &LoadOptions) -> anyhow::Result<PocketScionConfig> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for path in paths {
+        if path != "-" && Path::new(path).is_dir() {
+            files.extend(config_files_in_dir(path)?);
+            dirs.push(PathBuf::from(path));
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut config = merge_config_files(&files, options)?;
+    // Adding or removing a file changes the directory, which is watched with --watch
+    config.sources.extend(dirs);
+    log_disabled(&config);
+    Ok(config)
+}
+
+/// Log the SNAPs, endhost APIs and routers with `enabled` set to false. They stay in the config,
+/// so that the other entries keep their index in errors, logs and token files, and are skipped
+/// when the simulator is built.
+fn log_disabled(config: &PocketScionConfig) {
+    fn log<T>(
+        list: &Option<Vec<T>>,
+        name: &str,
+        enabled: impl Fn(&T) -> bool,
+        describe: impl Fn(&T) -> String,
+    ) {
+        for (index, entry) in list.iter().flatten().enumerate() {
+            if !enabled(entry) {
+                tracing::info!("Skipping disabled {name}[{index}] {}", describe(entry));
+            }
+        }
+    }
+
+    log(
+        &config.snaps,
+        "snaps",
+        |snap| snap.enabled,
+        |snap| format!("at {}", snap.listening_addr),
+    );
+    log(
+        &config.endhost_apis,
+        "endhost_apis",
+        |api| api.enabled,
+        |api| format!("at {}", api.listening_addr),
+    );
+    log(
+        &config.routers,
+        "routers",
+        |router| router.enabled,
+        |router| format!("of AS {} at {}", router.isd_as, router.listening_addr),
+    );
+}
+
+/// The JSON and YAML files in `dir`, sorted by name
+fn config_files_in_dir(dir: &str) -> anyhow::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).context(format!("Failed to read config directory: {dir}"))?
+    {
+        let path = entry
+            .context(format!("Failed to read config directory: {dir}"))?
+            .path();
+        if path.is_file() && ConfigFormat::from_path(&path).is_some() {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!("No JSON or YAML config files in directory: {dir}");
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Read the config files at `paths` and merge them in order, with [`merge_values`]. A single
+/// config is read with [`load_config`], which reports errors with their position in the file.
+fn merge_config_files(
+    paths: &[String],
+    options: &LoadOptions,
+) -> anyhow::Result<PocketScionConfig> {
+    let [first, rest @ ..] = paths else {
+        anyhow::bail!("No config file given");
+    };
+    if rest.is_empty() {
+        return load_config(first, options);
+    }
+
+    let mut merged = serde_json::Value::Object(Default::default());
+    let mut chain = Vec::new();
+    for path in paths {
+        let (value, canonical) = read_config_value(path, options)?;
+        merge_values(&mut merged, value);
+        chain.extend(canonical);
+    }
+
+    let mut config: PocketScionConfig =
+        from_value(merged, "merged config", options.unknown_fields)?;
+    config.sources.clone_from(&chain);
+    // The includes of every file were made relative to the working directory when reading it
+    let includes = std::mem::take(&mut config.includes);
+    merge_includes(&mut config, includes, Path::new("."), &mut chain, options)?;
+
+    Ok(config)
+}
+
+/// Read and expand the config at `path`, or from stdin if `path` is `-`, without interpreting it.
+/// The `includes` are rewritten to be relative to the working directory. Returns the config and
+/// the canonical path of the file.
+fn read_config_value(
+    path: &str,
+    options: &LoadOptions,
+) -> anyhow::Result<(serde_json::Value, Option<PathBuf>)> {
+    if path == "-" {
+        return Ok((read_stdin(options)?, None));
+    }
+    if let Some(url) = config_url(path) {
+        return Ok((fetch_config(&url, options)?, None));
+    }
+
+    tracing::info!(config = %path, "Reading config");
+    let path = Path::new(path);
+    let mut value: serde_json::Value =
+        read_config_file(path, "config file", options.format, options)?;
+    let path = path
+        .canonicalize()
+        .context(format!("Failed to resolve config file: {}", path.display()))?;
+
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    if let Some(serde_json::Value::Array(includes)) = value.get_mut("includes") {
+        for include in includes {
+            if let serde_json::Value::String(include) = include {
+                *include = dir.join(&*include).to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    Ok((value, Some(path)))
+}
+
+/// Merge `overlay` into `base`: objects are merged key by key, lists are concatenated and any
+/// other value of `overlay` replaces the one in `base`
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    use serde_json::Value;
+
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) => base.extend(overlay),
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Read and parse the config from `path`, or from stdin if `path` is `-`, and merge all fragments
+/// it includes
+fn load_config(path: &str, options: &LoadOptions) -> anyhow::Result<PocketScionConfig> {
+    let unsourced: Option<PocketScionConfig> = if path == "-" {
+        Some(read_stdin(options)?)
+    } else if let Some(url) = config_url(path) {
+        Some(fetch_config(&url, options)?)
+    } else {
+        None
+    };
+    if let Some(mut config) = unsourced {
+        // Includes of stdin and of a fetched config are relative to the working directory
+        let includes = std::mem::take(&mut config.includes);
+        merge_includes(
+            &mut config,
+            includes,
+            Path::new("."),
+            &mut Vec::new(),
+            options,
+        )?;
+        return Ok(config);
+    }
+
+    tracing::info!(config = %path, "Reading config");
+    let path = Path::new(path);
+    let mut config: PocketScionConfig =
+        read_config_file(path, "config file", options.format, options)?;
+    config.sources.push(path.to_owned());
+
+    let includes = std::mem::take(&mut config.includes);
+    if !includes.is_empty() {
+        let path = path
+            .canonicalize()
+            .context(format!("Failed to resolve config file: {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("/")).to_owned();
+        merge_includes(&mut config, includes, &dir, &mut vec![path], options)?;
+    }
+
+    Ok(config)
+}
+
+/// Read, expand and parse the config from stdin
+fn read_stdin<T: DeserializeOwned>(options: &LoadOptions) -> anyhow::Result<T> {
+    tracing::info!("Reading config from stdin");
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .context("Failed to read config from stdin")?;
+    let content = substitute_env_vars(&content, &options.vars).context("Failed to expand stdin")?;
+    parse_config(&content, options.format, "stdin", options.unknown_fields)
+}
+
+/// Where the config at `path` comes from if it is not a file that can be watched and read again,
+/// like "read from stdin"
+pub fn unwatchable_source(path: &str) -> Option<&'static str> {
+    if path == "-" {
+        Some("read from stdin")
+    } else if config_url(path).is_some() {
+        Some("fetched from a URL")
+    } else {
+        None
+    }
+}
+
+/// The URL in `path` if it is an `http` or `https` URL, which is fetched instead of read from disk
+fn config_url(path: &str) -> Option<reqwest::Url> {
+    let url = reqwest::Url::parse(path).ok()?;
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+/// Fetch, expand and parse the config at `url`. Unless `format` is given, the format is taken from
+/// the extension of the URL path, or else from the `Content-Type` of the response.
+fn fetch_config<T: DeserializeOwned>(
+    url: &reqwest::Url,
+    options: &LoadOptions,
+) -> anyhow::Result<T> {
+    tracing::info!(config = %url, "Fetching config");
+    // The blocking client can not be used on a thread of the tokio runtime
+    let fetch_url = url.clone();
+    let (content, content_type) = std::thread::spawn(move || {
+        let response = reqwest::blocking::get(fetch_url)?.error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        Ok::<_, reqwest::Error>((response.text()?, content_type))
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("Fetching the config panicked"))?
+    .context(format!("Failed to fetch config: {url}"))?;
+
+    let format = options
+        .format
+        .or_else(|| ConfigFormat::from_path(Path::new(url.path())))
+        .or_else(|| {
+            content_type
+                .as_deref()
+                .and_then(ConfigFormat::from_content_type)
+        });
+    let content = substitute_env_vars(&content, &options.vars)
+        .context(format!("Failed to expand config: {url}"))?;
+    parse_config(&content, format, url.as_str(), options.unknown_fields)
+}
+
+/// Read, expand and parse a config file or fragment, in `format` or else the format of its
+/// extension. `source` describes the kind of file in error messages.
+fn read_config_file<T: DeserializeOwned>(
+    path: &Path,
+    source: &str,
+    format: Option<ConfigFormat>,
+    options: &LoadOptions,
+) -> anyhow::Result<T> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read {source}: {}", path.display()))?;
+    let content = substitute_env_vars(&content, &options.vars)
+        .context(format!("Failed to expand {source}: {}", path.display()))?;
+    parse_config(
+        &content,
+        format.or_else(|| ConfigFormat::from_path(path)),
+        source,
+        options.unknown_fields,
+    )
+}
+
+/// Append the fragments listed in `includes` to `config`, depth first and in the listed order.
+/// The paths are relative to `dir`, the directory of the including file. `chain` holds the files
+/// that are currently being included, to detect cycles.
+fn merge_includes(
+    config: &mut PocketScionConfig,
+    includes: Vec<String>,
+    dir: &Path,
+    chain: &mut Vec<PathBuf>,
+    options: &LoadOptions,
+) -> anyhow::Result<()> {
+    for include in includes {
+        let path = dir.join(&include);
+        let path = path
+            .canonicalize()
+            .context(format!("Failed to resolve include: {}", path.display()))?;
+
+        if let Some(start) = chain.iter().position(|included| *included == path) {
+            let cycle: Vec<String> = chain[start..]
+                .iter()
+                .chain([&path])
+                .map(|included| included.display().to_string())
+                .collect();
+            anyhow::bail!("Include cycle: {}", cycle.join(" -> "));
+        }
+
+        tracing::info!("Including config fragment: {}", path.display());
+        let mut fragment: ConfigFragment =
+            read_config_file(&path, "config fragment", None, options)?;
+        let nested = std::mem::take(&mut fragment.includes);
+        fragment.append_to(config);
+        config.sources.push(path.clone());
+
+        let fragment_dir = path.parent().unwrap_or(Path::new("/")).to_owned();
+        chain.push(path);
+        merge_includes(config, nested, &fragment_dir, chain, options)?;
+        chain.pop();
+    }
+
+    Ok(())
+}
+
+/// Replace all `${VAR}` references in the raw config text with the value of `VAR` in `vars`, or
+/// else of the environment variable `VAR`. Values are inserted verbatim, before the text is
+/// parsed. `$${` is an escape for a literal `${`.
+fn substitute_env_vars(content: &str, vars: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("${") {
+        let offset = content.len() - rest.len() + start;
+        if let Some(before) = rest[..start].strip_suffix('$') {
+            result.push_str(before);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+
+        let reference = &rest[start + 2..];
+        let end = reference.find('}').with_context(|| {
+            format!(
+                "Unterminated variable reference at {}",
+                text_location(content, offset)
+            )
+        })?;
+        let name = &reference[..end];
+
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            anyhow::bail!(
+                "Invalid variable name '{name}' at {}",
+                text_location(content, offset)
+            );
+        }
+
+        let value = match vars.get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var(name).map_err(|_| {
+                anyhow::anyhow!(
+                    "Variable {name} referenced at {} is not set, neither as a config variable \
+                     nor in the environment",
+                    text_location(content, offset)
+                )
+            })?,
+        };
+        result.push_str(&value);
+
+        rest = &reference[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Read the variables of a JSON or YAML file holding a map from variable names to strings,
+/// numbers or booleans, for `${NAME}` references in configs
+pub fn load_vars(path: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let content =
+        std::fs::read_to_string(path).context(format!("Failed to read variables file: {path}"))?;
+    let vars: BTreeMap<String, serde_json::Value> = parse_syntax(
+        &content,
+        ConfigFormat::from_path(Path::new(path)),
+        "variables file",
+    )?;
+    vars.into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                _ => anyhow::bail!("Variable {name} in {path} must be a string, number or boolean"),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Describe the position of byte `offset` in `content` as line and column
+fn text_location(content: &str, offset: usize) -> String {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    format!("line {line}, column {column}")
+}
+
+/// Parse the config content in the given format. If the format is unknown, JSON is tried first,
+/// then YAML. `source` describes where the content came from in error messages.
+fn parse_config<T: DeserializeOwned>(
+    content: &str,
+    format: Option<ConfigFormat>,
+    source: &str,
+    unknown_fields: UnknownFields,
+) -> anyhow::Result<T> {
+    match unknown_fields {
+        UnknownFields::Deny => parse_syntax(content, format, source),
+        UnknownFields::Warn => {
+            let value = parse_syntax(content, format, source)?;
+            from_value(value, source, unknown_fields)
+        }
+    }
+}
+
+/// Parse the content in the given format, or as JSON and then as YAML if the format is unknown
+fn parse_syntax<T: DeserializeOwned>(
+    content: &str,
+    format: Option<ConfigFormat>,
+    source: &str,
+) -> anyhow::Result<T> {
+    match format {
+        Some(ConfigFormat::Json) => {
+            parse_json(content).context(format!("Failed to parse {source} as JSON"))
+        }
+        Some(ConfigFormat::Yaml) => {
+            serde_yaml_ng::from_str(content).context(format!("Failed to parse {source} as YAML"))
+        }
+        None => parse_json(content).or_else(|json_err| {
+            serde_yaml_ng::from_str(content).map_err(|yaml_err| {
+                anyhow::anyhow!(
+                    "Failed to parse {source} as JSON ({json_err}) or YAML ({yaml_err})"
+                )
+            })
+        }),
+    }
+}
+
+/// Parse JSON like `serde_json::from_str`, but name the element that an error is about, as
+/// serde_yaml_ng does for YAML
+fn parse_json<T: DeserializeOwned>(content: &str) -> anyhow::Result<T> {
+    let mut deserializer = serde_json::Deserializer::from_str(content);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(path_error)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Prefix the error with the element of the config it is about, such as `topology.links[1]`
+fn path_error<E: std::error::Error + Send + Sync + 'static>(
+    error: serde_path_to_error::Error<E>,
+) -> anyhow::Error {
+    let path = error.path().to_string();
+    if path == "." {
+        anyhow::Error::new(error.into_inner())
+    } else {
+        anyhow::anyhow!("{}: {}", path, error.into_inner())
+    }
+}
+
+/// Interpret an already parsed config. With [`UnknownFields::Warn`], every unknown field is
+/// removed from `value` with a warning until the config can be read.
+fn from_value<T: DeserializeOwned>(
+    mut value: serde_json::Value,
+    source: &str,
+    unknown_fields: UnknownFields,
+) -> anyhow::Result<T> {
+    loop {
+        let error = match serde_path_to_error::deserialize(&value) {
+            Ok(config) => return Ok(config),
+            Err(error) => error,
+        };
+        // serde only reports unknown fields in its error message
+        let unknown = error.inner().to_string().starts_with("unknown field");
+        if unknown_fields == UnknownFields::Deny
+            || !unknown
+            || !remove_field(&mut value, error.path())
+        {
+            return Err(path_error(error)).context(format!("Failed to parse {source}"));
+        }
+        tracing::warn!("Ignoring unknown field {} in {}", error.path(), source);
+    }
+}
+
+/// Remove the field at `path` from `value`, returns whether it existed
+fn remove_field(value: &mut serde_json::Value, path: &serde_path_to_error::Path) -> bool {
+    use serde_path_to_error::Segment;
+
+    let segments: Vec<&Segment> = path.iter().collect();
+    let Some((Segment::Map { key }, parents)) = segments.split_last() else {
+        return false;
+    };
+    let mut current = value;
+    for segment in parents {
+        let next = match segment {
+            Segment::Seq { index } => current.get_mut(*index),
+            Segment::Map { key } => current.get_mut(key.as_str()),
+            _ => None,
+        };
+        match next {
+            Some(next) => current = next,
+            None => return false,
+        }
+    }
+    current
+        .as_object_mut()
+        .is_some_and(|object| object.remove(key.as_str()).is_some())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PocketScionConfig {
+    /// Paths of config fragments to merge into this config, relative to this file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+    /// The files this config was read from, including all fragments
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+    /// The SCION network topology being simulated
+    pub topology: TopologyConfig,
+    /// SCION Network Access Points (SNAP) for the server and client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snaps: Option<Vec<SnapConfig>>,
+    /// Optional endhost API configurations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endhost_apis: Option<Vec<EndhostApiConfig>>,
+    /// Optional router configurations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routers: Option<Vec<RouterConfig>>,
+    /// Management API listen address, an ephemeral port on localhost if not set
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_optional_listen_addr"
+    )]
+    #[schemars(with = "Option<SocketAddress>")]
+    pub management_listen_addr: Option<SocketAddr>,
+    /// Optional listen address for serving Prometheus metrics on `/metrics`
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_optional_listen_addr"
+    )]
+    #[schemars(with = "Option<SocketAddress>")]
+    pub metrics_listen_addr: Option<SocketAddr>,
+    /// Optional listen address for serving health checks on `/healthz`
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_optional_listen_addr"
+    )]
+    #[schemars(with = "Option<SocketAddress>")]
+    pub health_listen_addr: Option<SocketAddr>,
+    /// Optional listen address for serving the control API, to change the running topology
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_optional_listen_addr"
+    )]
+    #[schemars(with = "Option<SocketAddress>")]
+    pub control_listen_addr: Option<SocketAddr>,
+    /// Optional token that requests to the control API must send as `Authorization: Bearer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_auth_token: Option<String>,
+    /// Optional log levels, used unless logging is configured on the command line or RUST_LOG
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingConfig>,
+}
+
+/// Log levels of a config
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Level of all modules that are not listed in `modules`, info if not set. One of off, error,
+    /// warn, info, debug and trace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_level: Option<String>,
+    /// Levels by module path, like `"pocketscion": "warn"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, String>,
+}
+
+impl LoggingConfig {
+    /// The log filter with these levels
+    pub fn env_filter(&self) -> anyhow::Result<EnvFilter> {
+        let default_level = self.default_level.as_deref().unwrap_or("info");
+        let default_level: LevelFilter = default_level
+            .parse()
+            .context(format!("Invalid logging.default_level '{default_level}'"))?;
+
+        let mut filter = EnvFilter::default().add_directive(default_level.into());
+        for (module, level) in &self.modules {
+            let level: LevelFilter = level.parse().context(format!(
+                "Invalid level '{level}' of module '{module}' in logging.modules"
+            ))?;
+            let directive = format!("{module}={level}")
+                .parse()
+                .context(format!("Invalid module '{module}' in logging.modules"))?;
+            filter = filter.add_directive(directive);
+        }
+        Ok(filter)
+    }
+}
+
+/// A part of a config that is merged into another config with `includes`. All lists are appended
+/// to the lists of the including config.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFragment {
+    /// Paths of further config fragments to merge, relative to this file
+    #[serde(default)]
+    includes: Vec<String>,
+    #[serde(default)]
+    topology: TopologyFragment,
+    #[serde(default)]
+    snaps: Vec<SnapConfig>,
+    #[serde(default)]
+    endhost_apis: Vec<EndhostApiConfig>,
+    #[serde(default)]
+    routers: Vec<RouterConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TopologyFragment {
+    #[serde(default)]
+    ases: Vec<AsConfig>,
+    #[serde(default)]
+    links: Vec<LinkConfig>,
+}
+
+impl PocketScionConfig {
+    /// The SNAPs that are not disabled, with their index in `snaps`
+    pub fn enabled_snaps(&self) -> impl Iterator<Item = (usize, &SnapConfig)> {
+        enabled_entries(&self.snaps, |snap| snap.enabled)
+    }
+
+    /// The endhost APIs that are not disabled, with their index in `endhost_apis`
+    pub fn enabled_endhost_apis(&self) -> impl Iterator<Item = (usize, &EndhostApiConfig)> {
+        enabled_entries(&self.endhost_apis, |api| api.enabled)
+    }
+
+    /// The routers that are not disabled, with their index in `routers`
+    pub fn enabled_routers(&self) -> impl Iterator<Item = (usize, &RouterConfig)> {
+        enabled_entries(&self.routers, |router| router.enabled)
+    }
+}
+
+/// The entries of `list` for which `is_enabled` holds, with their index in the list
+fn enabled_entries<T>(
+    list: &Option<Vec<T>>,
+    is_enabled: impl Fn(&T) -> bool,
+) -> impl Iterator<Item = (usize, &T)> {
+    list.iter()
+        .flatten()
+        .enumerate()
+        .filter(move |(_, entry)| is_enabled(entry))
+}
+
+impl ConfigFragment {
+    fn append_to(self, config: &mut PocketScionConfig) {
+        fn append<T>(list: &mut Option<Vec<T>>, items: Vec<T>) {
+            if !items.is_empty() {
+                list.get_or_insert_default().extend(items);
+            }
+        }
+
+        config.topology.ases.extend(self.topology.ases);
+        config.topology.links.extend(self.topology.links);
+        append(&mut config.snaps, self.snaps);
+        append(&mut config.endhost_apis, self.endhost_apis);
+        append(&mut config.routers, self.routers);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TopologyConfig {
+    /// List of ASes in the topology
+    pub ases: Vec<AsConfig>,
+    /// List of links between ASes
+    pub links: Vec<LinkConfig>,
+}
+
+/// A link between two ASes, either as a string (e.g., "1-11#1 core 1-12#1") or as an object with
+/// link attributes
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum LinkConfig {
+    Plain(#[schemars(with = "Link")] String),
+    Detailed(DetailedLinkConfig),
+}
+
+// Deserialized by hand instead of as an untagged enum, so that errors in a link object, such as an
+// unknown field, are reported instead of "data did not match any variant"
+impl<'de> Deserialize<'de> for LinkConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LinkVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for LinkVisitor {
+            type Value = LinkConfig;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a link string or an object with a link and its attributes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, link: &str) -> Result<LinkConfig, E> {
+                Ok(LinkConfig::Plain(link.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<LinkConfig, A::Error> {
+                let deserializer = serde::de::value::MapAccessDeserializer::new(map);
+                DetailedLinkConfig::deserialize(deserializer)
+                    .map(LinkConfig::Detailed)
+                    .map_err(|e| {
+                        if e.to_string().starts_with("missing field `link`") {
+                            serde::de::Error::custom(
+                                "link object without a `link` field, expected the link like \
+                                 {\"link\": \"1-1#1 core 1-2#1\"}",
+                            )
+                        } else {
+                            e
+                        }
+                    })
+            }
+        }
+
+        deserializer.deserialize_any(LinkVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DetailedLinkConfig {
+    /// The link in the string format, or only its two interfaces (e.g., "1-11#1 1-12#1") if
+    /// `relationship` is set
+    #[schemars(schema_with = "crate::schema::detailed_link")]
+    pub link: String,
+    /// Relationship of the first AS of the link to the second, must match the type of `link`
+    /// if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<LinkRelationship>,
+    /// One-way latency in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u32>,
+    /// Variation of the latency in milliseconds, requires `latency_ms` and is at most as large
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter_ms: Option<u32>,
+    /// Bandwidth limit in bits per second, unlimited if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub bandwidth_bps: Option<u64>,
+    /// MTU in bytes, the default of pocketscion if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 576))]
+    pub mtu: Option<u16>,
+}
+
+/// Relationship of the first AS of a link to the second AS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkRelationship {
+    /// A link between two core ASes
+    Core,
+    /// The first AS is the parent of the second
+    Parent,
+    /// The first AS is the child of the second
+    Child,
+    /// A peering link
+    Peer,
+}
+
+impl LinkRelationship {
+    /// The link type of the string format
+    pub fn link_type(self) -> &'static str {
+        match self {
+            LinkRelationship::Core => "core",
+            LinkRelationship::Parent => "parent_of",
+            LinkRelationship::Child => "child_of",
+            LinkRelationship::Peer => "peer",
+        }
+    }
+
+    /// The relationship of a link type of the string format, including its aliases
+    pub fn of_link_type(link_type: &str) -> Option<Self> {
+        match link_type.to_lowercase().as_str() {
+            "core" => Some(LinkRelationship::Core),
+            "parent_of" | "down_to" => Some(LinkRelationship::Parent),
+            "child_of" | "up_to" => Some(LinkRelationship::Child),
+            "peer" => Some(LinkRelationship::Peer),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LinkRelationship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LinkRelationship::Core => "core",
+            LinkRelationship::Parent => "parent",
+            LinkRelationship::Child => "child",
+            LinkRelationship::Peer => "peer",
+        })
+    }
+}
+
+impl LinkConfig {
+    /// The link in the string format. A link object that only names its interfaces gets the
+    /// link type of its relationship.
+    pub fn link(&self) -> Cow<'_, str> {
+        match self {
+            LinkConfig::Plain(link) => Cow::Borrowed(link),
+            LinkConfig::Detailed(detailed) => {
+                let parts: Vec<&str> = detailed.link.split_whitespace().collect();
+                match (parts.as_slice(), detailed.relationship) {
+                    ([from, to], Some(relationship)) => {
+                        Cow::Owned(format!("{from} {} {to}", relationship.link_type()))
+                    }
+                    _ => Cow::Borrowed(&detailed.link),
+                }
+            }
+        }
+    }
+
+    /// The link attributes, if the link is given as an object
+    pub fn attributes(&self) -> Option<&DetailedLinkConfig> {
+        match self {
+            LinkConfig::Plain(_) => None,
+            LinkConfig::Detailed(detailed) => Some(detailed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AsConfig {
+    /// ISD-AS identifier (e.g., "1-11")
+    #[schemars(with = "IsdAs")]
+    pub isd_as: String,
+    /// Whether this AS is a core AS
+    pub is_core: bool,
+    /// MTU in bytes of the links of this AS that do not set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 576))]
+    pub default_mtu: Option<u16>,
+    /// Bandwidth limit in bits per second of the links of this AS that do not set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub default_bandwidth_bps: Option<u64>,
+}
+
+/// SCION Network Access Point (SNAP) configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SnapConfig {
+    /// Listening address for the SNAP's control plane
+    #[serde(deserialize_with = "deserialize_listen_addr")]
+    #[schemars(with = "SocketAddress")]
+    pub listening_addr: SocketAddr,
+    /// This SNAP's data plane
+    pub data_plane: DataPlaneConfig,
+    /// Set to false to leave this SNAP out without removing it from the config
+    #[serde(default = "enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DataPlaneConfig {
+    /// ISD-AS identifier for this data plane
+    #[schemars(with = "IsdAs")]
+    pub isd_as: String,
+    /// The LAN address this data plane should listen on
+    #[serde(deserialize_with = "deserialize_listen_addr")]
+    #[schemars(with = "SocketAddress")]
+    pub listening_addr: SocketAddr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EndhostApiConfig {
+    /// ISDs this endhost API serves
+    #[schemars(with = "Vec<IsdAs>")]
+    pub isds: Vec<String>,
+    /// Listening address for the endhost API
+    #[serde(deserialize_with = "deserialize_listen_addr")]
+    #[schemars(with = "SocketAddress")]
+    pub listening_addr: SocketAddr,
+    /// Set to false to leave this endhost API out without removing it from the config
+    #[serde(default = "enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RouterConfig {
+    /// ISD-AS identifier
+    #[schemars(with = "IsdAs")]
+    pub isd_as: String,
+    /// Interface IDs, non-zero and each listed once
+    #[schemars(inner(range(min = 1)))]
+    pub interfaces: Vec<u16>,
+    /// Listening address
+    #[serde(deserialize_with = "deserialize_listen_addr")]
+    #[schemars(with = "SocketAddress")]
+    pub listening_addr: SocketAddr,
+    /// SNAP data plane exclude addresses, a bare IP is a network with a single address
+    #[serde(default, deserialize_with = "deserialize_networks")]
+    #[schemars(with = "Vec<IpNetwork>")]
+    pub snap_data_plane_excludes: Vec<IpNet>,
+    /// SNAP data plane interfaces
+    #[serde(default, deserialize_with = "deserialize_addr_map")]
+    #[schemars(with = "BTreeMap<String, SocketAddress>")]
+    pub snap_data_plane_interfaces: BTreeMap<String, SocketAddr>,
+    /// Set to false to leave this router out without removing it from the config
+    #[serde(default = "enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+/// Default of the `enabled` flags
+fn enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+/// Parse a socket address, with an error that names what is wrong with it. IPv6 addresses must
+/// be in brackets, and a scope ID is only allowed as the numeric interface index of a link-local
+/// address, like `[fe80::1%2]:8082`.
+fn parse_socket_addr(addr: &str) -> Result<SocketAddr, String> {
+    if let Ok(parsed) = addr.parse::<SocketAddr>() {
+        return match parsed {
+            SocketAddr::V6(v6) if v6.scope_id() != 0 && !v6.ip().is_unicast_link_local() => {
+                Err(format!(
+                    "invalid address '{addr}', a scope ID is only allowed for link-local IPv6 addresses (fe80::/10)"
+                ))
+            }
+            parsed => Ok(parsed),
+        };
+    }
+
+    let message = if !addr.starts_with('[') && addr.matches(':').count() > 1 {
+        format!(
+            "invalid address '{addr}', IPv6 addresses must be written in brackets, like [::1]:8082"
+        )
+    } else if addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<std::net::IpAddr>()
+        .is_ok()
+    {
+        format!("invalid address '{addr}', the port is missing")
+    } else if let Some((_, scope)) = addr
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(ip, _)| ip.split_once('%'))
+    {
+        format!(
+            "invalid address '{addr}', the scope ID '{scope}' must be a numeric interface index, like [fe80::1%2]:8082"
+        )
+    } else {
+        format!(
+            "invalid address '{addr}', expected an IP address and port like 127.0.0.1:8082 or [::1]:8082"
+        )
+    };
+    Err(message)
+}
+
+/// Deserialize a listen address with [`parse_socket_addr`]
+fn deserialize_listen_addr<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SocketAddr, D::Error> {
+    let addr = String::deserialize(deserializer)?;
+    parse_socket_addr(&addr).map_err(serde::de::Error::custom)
+}
+
+/// Deserialize an optional listen address with [`parse_socket_addr`]
+fn deserialize_optional_listen_addr<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SocketAddr>, D::Error> {
+    let addr: Option<String> = Option::deserialize(deserializer)?;
+    addr.map(|addr| parse_socket_addr(&addr).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Deserialize a map to socket addresses with [`parse_socket_addr`]
+fn deserialize_addr_map<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, SocketAddr>, D::Error> {
+    let addrs: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
+    addrs
+        .into_iter()
+        .map(|(key, addr)| {
+            Ok((
+                key,
+                parse_socket_addr(&addr).map_err(serde::de::Error::custom)?,
+            ))
+        })
+        .collect()
+}
+
+/// Deserialize a list of IP networks in CIDR notation, where a bare IP stands for the network of
+/// only that address (`/32` for IPv4, `/128` for IPv6)
+fn deserialize_networks<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<IpNet>, D::Error> {
+    let networks: Vec<String> = Vec::deserialize(deserializer)?;
+    networks
+        .iter()
+        .map(|network| {
+            network
+                .parse::<IpNet>()
+                .or_else(|_| network.parse::<std::net::IpAddr>().map(IpNet::from))
+                .map_err(|_| {
+                    serde::de::Error::custom(format!(
+                        "invalid IP network '{network}', expected an IP address or CIDR notation"
+                    ))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for the files of test `name`
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "pocketscion-configurator-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn router_with_excludes(excludes: &[&str]) -> serde_json::Result<RouterConfig> {
+        serde_json::from_value(serde_json::json!({
+            "isd_as": "1-1",
+            "interfaces": [1],
+            "listening_addr": "127.0.0.1:10122",
+            "snap_data_plane_excludes": excludes,
+        }))
+    }
+
+    #[test]
+    fn networks_accept_bare_ips_and_cidrs() {
+        let router =
+            router_with_excludes(&["10.0.0.1", "fd00::1", "10.1.0.0/16", "fd00:1::/64"]).unwrap();
+
+        let excludes: Vec<String> = router
+            .snap_data_plane_excludes
+            .iter()
+            .map(IpNet::to_string)
+            .collect();
+        assert_eq!(
+            excludes,
+            ["10.0.0.1/32", "fd00::1/128", "10.1.0.0/16", "fd00:1::/64"]
+        );
+    }
+
+    #[test]
+    fn networks_reject_invalid_prefix() {
+        let error = router_with_excludes(&["10.0.0.0/33"]).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("invalid IP network '10.0.0.0/33'"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn scoped_ipv6_listen_addr_keeps_its_scope() {
+        let SocketAddr::V6(addr) = parse_socket_addr("[fe80::1%2]:8082").unwrap() else {
+            panic!("not an IPv6 address");
+        };
+        assert_eq!(addr.scope_id(), 2);
+        assert_eq!(addr.port(), 8082);
+
+        assert!(parse_socket_addr("[fd00::1%2]:8082").is_err());
+    }
+
+    #[test]
+    fn ipv6_listen_addrs_survive_dump_config() {
+        let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+            "topology": {"ases": [{"isd_as": "1-1", "is_core": true}], "links": []},
+            "management_listen_addr": "[fe80::1%2]:8082",
+            "control_listen_addr": "[::1]:0",
+        }))
+        .unwrap();
+
+        // --dump-config prints the config with serde_json::to_string_pretty
+        let dumped = serde_json::to_string_pretty(&config).unwrap();
+        let reparsed: PocketScionConfig = serde_json::from_str(&dumped).unwrap();
+        assert_eq!(
+            reparsed.management_listen_addr,
+            config.management_listen_addr
+        );
+        assert_eq!(reparsed.control_listen_addr, config.control_listen_addr);
+        assert!(dumped.contains("[fe80::1%2]:8082"), "{dumped}");
+    }
+
+    #[test]
+    fn link_errors_name_their_position() {
+        let config = serde_json::json!({
+            "topology": {
+                "ases": [
+                    {"isd_as": "1-1", "is_core": true},
+                    {"isd_as": "1-2", "is_core": true},
+                    {"isd_as": "1-3", "is_core": false},
+                ],
+                "links": [
+                    "1-1#1 core 1-2#1",
+                    {"link": "1-1#2 parent_of 1-3#1", "latency_ms": 5},
+                    {"latency_ms": 5},
+                ],
+            },
+        });
+
+        let valid: serde_json::Value = {
+            let mut valid = config.clone();
+            valid["topology"]["links"].as_array_mut().unwrap().pop();
+            valid
+        };
+        let parsed: PocketScionConfig =
+            from_value(valid, "test.json", UnknownFields::Deny).unwrap();
+        assert!(matches!(parsed.topology.links[0], LinkConfig::Plain(_)));
+        assert!(matches!(parsed.topology.links[1], LinkConfig::Detailed(_)));
+
+        let error =
+            from_value::<PocketScionConfig>(config, "test.json", UnknownFields::Deny).unwrap_err();
+        let message = format!("{error:#}");
+        assert!(message.contains("topology.links[2]"), "{message}");
+        assert!(
+            message.contains("link object without a `link` field"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn vars_are_substituted_before_the_environment() {
+        let vars = BTreeMap::from([("ADDR".to_string(), "127.0.0.1:8082".to_string())]);
+        let content = r#"{"a": "${ADDR}", "b": "${PATH}"}"#;
+
+        let expanded = substitute_env_vars(content, &vars).unwrap();
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(
+            expanded,
+            format!(r#"{{"a": "127.0.0.1:8082", "b": "{path}"}}"#)
+        );
+
+        let vars = BTreeMap::from([("PATH".to_string(), "override".to_string())]);
+        assert_eq!(substitute_env_vars("${PATH}", &vars).unwrap(), "override");
+    }
+
+    #[test]
+    fn missing_and_invalid_vars_are_errors() {
+        let error = substitute_env_vars(
+            "{\n  \"a\": \"${POCKETSCION_UNSET_TEST_VAR}\"}",
+            &BTreeMap::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("POCKETSCION_UNSET_TEST_VAR referenced at line 2, column 9 is not set"),
+            "{error}"
+        );
+
+        let error = substitute_env_vars("${1X}", &BTreeMap::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid variable name '1X'"), "{error}");
+        let error = substitute_env_vars("${X", &BTreeMap::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unterminated variable reference"), "{error}");
+    }
+
+    #[test]
+    fn escaped_references_are_kept() {
+        let vars = BTreeMap::from([("X".to_string(), "1".to_string())]);
+        assert_eq!(
+            substitute_env_vars("$${X} ${X} $$ $", &vars).unwrap(),
+            "${X} 1 $$ $"
+        );
+    }
+
+    #[test]
+    fn include_cycles_name_the_chain() {
+        let dir = test_dir("include-cycle");
+        std::fs::write(
+            dir.join("main.json"),
+            r#"{"includes": ["frag.json"], "topology": {"ases": [], "links": []}}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("frag.json"), r#"{"includes": ["main.json"]}"#).unwrap();
+
+        let main = dir.join("main.json").to_string_lossy().into_owned();
+        let error = format!(
+            "{:#}",
+            load_configs(&[main], &LoadOptions::default()).unwrap_err()
+        );
+        let dir = dir.canonicalize().unwrap();
+        let chain = format!(
+            "Include cycle: {} -> {} -> {}",
+            dir.join("main.json").display(),
+            dir.join("frag.json").display(),
+            dir.join("main.json").display()
+        );
+        assert!(error.contains(&chain), "{error}");
+    }
+
+    #[test]
+    fn later_configs_extend_lists_and_override_values() {
+        let dir = test_dir("merge");
+        std::fs::write(
+            dir.join("base.json"),
+            r#"{
+                "topology": {"ases": [{"isd_as": "1-1", "is_core": true}], "links": []},
+                "management_listen_addr": "127.0.0.1:9000",
+                "metrics_listen_addr": "127.0.0.1:9001"
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("overlay.yaml"),
+            "topology:\n  ases:\n    - isd_as: 1-2\n      is_core: false\n  links:\n    - 1-1#1 parent_of 1-2#1\nmanagement_listen_addr: 127.0.0.1:9100\n",
+        )
+        .unwrap();
+
+        let paths =
+            ["base.json", "overlay.yaml"].map(|file| dir.join(file).to_string_lossy().into_owned());
+        let config = load_configs(&paths, &LoadOptions::default()).unwrap();
+
+        let ases: Vec<&str> = config
+            .topology
+            .ases
+            .iter()
+            .map(|as_config| as_config.isd_as.as_str())
+            .collect();
+        assert_eq!(ases, ["1-1", "1-2"]);
+        assert_eq!(config.topology.links.len(), 1);
+        assert_eq!(
+            config.management_listen_addr,
+            Some("127.0.0.1:9100".parse().unwrap())
+        );
+        assert_eq!(
+            config.metrics_listen_addr,
+            Some("127.0.0.1:9001".parse().unwrap())
+        );
+        assert_eq!(config.sources.len(), 2);
+    }
+
+    #[test]
+    fn nested_unknown_fields_are_removed_or_rejected() {
+        let config = serde_json::json!({
+            "topology": {
+                "ases": [{ "isd_as": "1-1", "is_core": true, "colour": "red" }],
+                "links": [],
+                "comment": "from a newer version"
+            }
+        });
+
+        let parsed: PocketScionConfig =
+            from_value(config.clone(), "test.json", UnknownFields::Warn).unwrap();
+        assert_eq!(parsed.topology.ases[0].isd_as, "1-1");
+
+        let error = format!(
+            "{:#}",
+            from_value::<PocketScionConfig>(config, "test.json", UnknownFields::Deny).unwrap_err()
+        );
+        assert!(error.contains("topology.ases[0]"), "{error}");
+        assert!(error.contains("unknown field `colour`"), "{error}");
+    }
+}
@@ -0,0 +1,527 @@
+//! Control API for changing the topology of the running simulator
+//!
+//! pocketscion 0.4 can not change the topology of a running simulator, apart from setting links
+//! up or down through its management API. The control API builds on that.
+
+use std::collections::{BTreeSet, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use endhost_api_models::PathDiscovery;
+use pocketscion::api::admin::client::ApiClient;
+use pocketscion::network::scion::topology::ScionLink;
+use pocketscion::state::endhost_segment_lister::StateEndhostSegmentLister;
+use pocketscion::state::{SharedPocketScionState, SystemState};
+use scion_proto::address::IsdAsn;
+use scion_proto::path::combinator;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::{AsConfig, TopologyConfig};
+
+/// State of the control API
+#[derive(Clone)]
+pub struct Control {
+    /// Client of the pocketscion management API
+    client: ApiClient,
+    /// The ASes of the topology, which can not change at runtime
+    ases: Arc<Vec<AsConfig>>,
+    /// The links of the topology that have not been removed. Locked while a link is changed in
+    /// pocketscion, so that the changes are applied in order.
+    links: Arc<Mutex<Links>>,
+    /// A copy of the state of the simulator to list path segments from, as pocketscion does not
+    /// share the state of a running simulator
+    state: SharedPocketScionState,
+    /// The bearer token every request must send, if the config sets one
+    auth_token: Option<Arc<str>>,
+}
+
+impl Control {
+    pub fn new(
+        client: ApiClient,
+        topology: &TopologyConfig,
+        state: SystemState,
+        auth_token: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let up = topology
+            .links
+            .iter()
+            .map(|link_config| {
+                let link: ScionLink = link_config.link().parse()?;
+                Ok(link.to_string())
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            client,
+            ases: Arc::new(topology.ases.clone()),
+            links: Arc::new(Mutex::new(Links {
+                up,
+                failed: BTreeSet::new(),
+            })),
+            state: SharedPocketScionState::from_system_state(state),
+            auth_token: auth_token.map(Arc::from),
+        })
+    }
+}
+
+/// The links that have not been removed, in the normalized string format
+struct Links {
+    up: BTreeSet<String>,
+    /// Links that are down until they are restored after a failure
+    failed: BTreeSet<String>,
+}
+
+#[derive(Deserialize)]
+struct LinkRequest {
+    /// The link in the string format of the config
+    link: String,
+}
+
+#[derive(Deserialize)]
+struct FailLinkRequest {
+    /// The link in the string format of the config
+    link: String,
+    /// How long the link is down before it is restored
+    duration_ms: u64,
+}
+
+/// The topology as it is currently simulated
+#[derive(Serialize)]
+struct TopologyResponse {
+    ases: Vec<AsConfig>,
+    /// The links that are up, in the normalized string format
+    links: Vec<String>,
+    /// The links that failed and are restored later
+    failed_links: Vec<String>,
+}
+
+/// The paths between two ASes
+#[derive(Serialize)]
+struct PathsResponse {
+    count: usize,
+    paths: Vec<PathResponse>,
+}
+
+/// A path between two ASes
+#[derive(Serialize)]
+struct PathResponse {
+    /// The interfaces of the path, from the source to the destination, like `1-1#2 1-2#1`. Left
+    /// out for a path without interface metadata.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interfaces: Option<String>,
+}
+
+/// Serve the control API at `addr` until the returned task is aborted, and return the bound
+/// address with the task
+pub async fn serve(
+    addr: SocketAddr,
+    control: Control,
+) -> anyhow::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .context(format!("Failed to bind control listener to {addr}"))?;
+    let addr = listener
+        .local_addr()
+        .context("Failed to get the control listen address")?;
+    tracing::info!(control_listen_addr = %addr, "Serving control API");
+
+    let app = router(control);
+    let server = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("Control server failed: {}", e);
+        }
+    });
+    Ok((addr, server))
+}
+
+/// The routes of the control API
+fn router(control: Control) -> Router {
+    Router::new()
+        .route("/topology", get(topology_handler))
+        .route("/links/remove", post(remove_link_handler))
+        .route("/links/fail", post(fail_link_handler))
+        .route("/paths/{src}/{dst}", get(paths_handler))
+        .route_layer(middleware::from_fn_with_state(
+            control.clone(),
+            require_auth_token,
+        ))
+        .with_state(control)
+}
+
+/// Reject requests without the bearer token of the config with `401 Unauthorized`, if the config
+/// sets one
+async fn require_auth_token(
+    State(control): State<Control>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(token) = &control.auth_token {
+        let sent = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !sent.is_some_and(|sent| tokens_match(sent, token)) {
+            tracing::warn!(
+                "Rejected control API request to {} without a valid token",
+                request.uri()
+            );
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "Missing or invalid bearer token\n",
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compare two tokens in a time that does not depend on the position of the first difference
+fn tokens_match(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Return the current topology, which reflects the links removed or failed through the control
+/// API
+async fn topology_handler(State(control): State<Control>) -> Json<TopologyResponse> {
+    let links = control.links.lock().await;
+    Json(TopologyResponse {
+        ases: control.ases.to_vec(),
+        links: links.up.iter().cloned().collect(),
+        failed_links: links.failed.iter().cloned().collect(),
+    })
+}
+
+/// Remove a link from the topology by setting it down in pocketscion
+async fn remove_link_handler(
+    State(control): State<Control>,
+    Json(request): Json<LinkRequest>,
+) -> (StatusCode, String) {
+    let (link, isd_as, if_id) = match parse_link(&request.link) {
+        Ok(parsed) => parsed,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("{e:#}\n")),
+    };
+
+    let mut links = control.links.lock().await;
+    // A failed link is already down, it is only not restored anymore
+    if !links.failed.remove(&link) {
+        if !links.up.contains(&link) {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("Link '{link}' is not in the topology\n"),
+            );
+        }
+        if let Err(e) = control.client.set_link_state(isd_as, if_id, false).await {
+            tracing::error!("Failed to remove link '{}': {}", link, e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to remove link '{link}': {e}\n"),
+            );
+        }
+        links.up.remove(&link);
+    }
+
+    tracing::info!("Removed link '{}'", link);
+    (StatusCode::OK, format!("Removed link '{link}'\n"))
+}
+
+/// Set a link down for a while, to model an outage. The link is restored by a task, so the
+/// response is sent right away.
+async fn fail_link_handler(
+    State(control): State<Control>,
+    Json(request): Json<FailLinkRequest>,
+) -> (StatusCode, String) {
+    let (link, isd_as, if_id) = match parse_link(&request.link) {
+        Ok(parsed) => parsed,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("{e:#}\n")),
+    };
+
+    let mut links = control.links.lock().await;
+    if links.failed.contains(&link) {
+        return (
+            StatusCode::CONFLICT,
+            format!("Link '{link}' has already failed\n"),
+        );
+    }
+    if !links.up.contains(&link) {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Link '{link}' is not in the topology\n"),
+        );
+    }
+    if let Err(e) = control.client.set_link_state(isd_as, if_id, false).await {
+        tracing::error!("Failed to fail link '{}': {}", link, e);
+        return (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to fail link '{link}': {e}\n"),
+        );
+    }
+    links.up.remove(&link);
+    links.failed.insert(link.clone());
+    drop(links);
+
+    tracing::info!("Failed link '{}' for {}ms", link, request.duration_ms);
+    tokio::spawn(restore_link(
+        control.clone(),
+        link.clone(),
+        isd_as,
+        if_id,
+        Duration::from_millis(request.duration_ms),
+    ));
+    (
+        StatusCode::OK,
+        format!("Failed link '{link}' for {}ms\n", request.duration_ms),
+    )
+}
+
+/// Set a failed link up again after `duration`, unless it was removed in the meantime
+async fn restore_link(
+    control: Control,
+    link: String,
+    isd_as: IsdAsn,
+    if_id: u16,
+    duration: Duration,
+) {
+    tokio::time::sleep(duration).await;
+
+    let mut links = control.links.lock().await;
+    if !links.failed.contains(&link) {
+        tracing::info!("Not restoring link '{}', it was removed", link);
+        return;
+    }
+    if let Err(e) = control.client.set_link_state(isd_as, if_id, true).await {
+        // The link stays down, as if it was removed
+        links.failed.remove(&link);
+        tracing::error!("Failed to restore link '{}': {}", link, e);
+        return;
+    }
+    links.failed.remove(&link);
+    links.up.insert(link.clone());
+    tracing::info!("Restored link '{}'", link);
+}
+
+/// Return the paths from `src` to `dst` that the endhost APIs can combine from the segments of
+/// the topology, without those across links removed or failed through the control API
+async fn paths_handler(
+    State(control): State<Control>,
+    Path((src, dst)): Path<(String, String)>,
+) -> Result<Json<PathsResponse>, (StatusCode, String)> {
+    let mut isd_ases = [src, dst].into_iter().map(|isd_as| {
+        let parsed: IsdAsn = isd_as.parse().map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid ISD-AS '{isd_as}': {e}\n"),
+            )
+        })?;
+        if !control
+            .ases
+            .iter()
+            .any(|as_config| as_config.isd_as.parse::<IsdAsn>().ok() == Some(parsed))
+        {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("AS {parsed} is not in the topology\n"),
+            ));
+        }
+        Ok(parsed)
+    });
+    let (src, dst) = (isd_ases.next().unwrap()?, isd_ases.next().unwrap()?);
+    if src == dst {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("The source and destination are both {src}\n"),
+        ));
+    }
+
+    let lister = StateEndhostSegmentLister::new(control.state.clone(), BTreeSet::from([src]));
+    let segments = lister
+        .list_segments(src, dst, i32::MAX, String::new())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list segments from {} to {}: {:?}", src, dst, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to list segments from {src} to {dst}: {e:?}\n"),
+            )
+        })?
+        .segments;
+
+    // Every interface belongs to exactly one link, so a path is up if all of its interfaces are
+    let interfaces: HashSet<String> = control
+        .links
+        .lock()
+        .await
+        .up
+        .iter()
+        .filter_map(|link| link.parse::<ScionLink>().ok())
+        .flat_map(|link| {
+            let (up, down) = link.get_up_and_downlink();
+            [up.to_string(), down.to_string()]
+        })
+        .collect();
+
+    let non_cores = segments
+        .up_segments
+        .into_iter()
+        .chain(segments.down_segments)
+        .collect();
+    let paths: Vec<PathResponse> = combinator::combine(src, dst, segments.core_segments, non_cores)
+        .iter()
+        .filter_map(|path| {
+            // A path without interface metadata can not be checked, it is returned without
+            // interfaces
+            let Some(path_interfaces) = path
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.interfaces.as_ref())
+            else {
+                tracing::debug!("Path from {} to {} has no interface metadata", src, dst);
+                return Some(PathResponse { interfaces: None });
+            };
+            let path_interfaces: Vec<String> = path_interfaces
+                .iter()
+                .map(|interface| format!("{}#{}", interface.isd_asn, interface.id))
+                .collect();
+            path_interfaces
+                .iter()
+                .all(|interface| interfaces.contains(interface))
+                .then(|| PathResponse {
+                    interfaces: Some(path_interfaces.join(" ")),
+                })
+        })
+        .collect();
+
+    Ok(Json(PathsResponse {
+        count: paths.len(),
+        paths,
+    }))
+}
+
+/// Parse a link string into its normalized form and one of its ends
+fn parse_link(link_str: &str) -> anyhow::Result<(String, IsdAsn, u16)> {
+    let link: ScionLink = link_str
+        .parse()
+        .context(format!("Invalid link '{link_str}'"))?;
+
+    let (end, _) = link.get_up_and_downlink();
+    let end = end.to_string();
+    let (isd_as, if_id) = end
+        .split_once('#')
+        .context(format!("Invalid link '{link_str}'"))?;
+
+    Ok((link.to_string(), isd_as.parse()?, if_id.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        ConfigBuilder, ConfigWarnings, build_runtime_state, shutdown_runtime, start_runtime,
+    };
+
+    /// Send a request with the token of the test to `app`, and return the status and JSON body
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn removed_and_failed_links_change_the_paths() {
+        let localhost = SocketAddr::from(([127, 0, 0, 1], 0));
+        let config = ConfigBuilder::new()
+            .with_as("1-1", true)
+            .with_as("1-2", false)
+            .with_link("1-1#1 parent_of 1-2#1")
+            .with_link("1-1#2 parent_of 1-2#2")
+            .with_snap(localhost, "1-2", localhost)
+            .build()
+            .unwrap();
+        let (state, _, _) = build_runtime_state(&config, ConfigWarnings::Log).unwrap();
+        let mut runtime = start_runtime(&config).await.unwrap();
+        let control = Control::new(
+            runtime.api_client(),
+            &config.topology,
+            state.system_state().clone(),
+            Some("secret".to_string()),
+        )
+        .unwrap();
+        let app = router(control);
+
+        let unauthorized = axum::http::Request::get("/topology")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(unauthorized).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let (status, paths) = send(&app, "GET", "/paths/1-2/1-1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(paths["count"], 2);
+
+        let link = json!({ "link": "1-1#1 parent_of 1-2#1" });
+        let (status, _) = send(&app, "POST", "/links/remove", Some(link.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "POST", "/links/remove", Some(link)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, paths) = send(&app, "GET", "/paths/1-2/1-1", None).await;
+        assert_eq!(paths["count"], 1);
+        let interfaces = paths["paths"][0]["interfaces"].as_str().unwrap();
+        assert!(
+            interfaces.contains("1-1#2") && !interfaces.contains("#1"),
+            "{interfaces}"
+        );
+
+        // A failed link that is removed is not restored
+        let link = json!({ "link": "1-1#2 parent_of 1-2#2", "duration_ms": 100 });
+        let (status, _) = send(&app, "POST", "/links/fail", Some(link)).await;
+        assert_eq!(status, StatusCode::OK);
+        let link = json!({ "link": "1-1#2 parent_of 1-2#2" });
+        let (status, _) = send(&app, "POST", "/links/remove", Some(link)).await;
+        assert_eq!(status, StatusCode::OK);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let (_, topology) = send(&app, "GET", "/topology", None).await;
+        assert_eq!(topology["links"], json!([]));
+        assert_eq!(topology["failed_links"], json!([]));
+        let (_, paths) = send(&app, "GET", "/paths/1-2/1-1", None).await;
+        assert_eq!(paths["count"], 0);
+
+        shutdown_runtime(&mut runtime, Duration::from_secs(5)).await;
+    }
+}
@@ -0,0 +1,313 @@
+//! Semantic diff of two configs, which ignores formatting and the order of list entries
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Context;
+use pocketscion::network::scion::topology::ScionLink;
+use serde::Serialize;
+
+use crate::dot::attributes_label;
+use crate::{PocketScionConfig, link_attributes, parse_isd_as};
+
+/// The ASes, links, SNAPs, endhost APIs and routers that differ between two configs. Serialized
+/// with a section for each, even if it has no differences.
+#[derive(Serialize)]
+pub struct ConfigDiff {
+    pub ases: SectionDiff,
+    pub links: SectionDiff,
+    pub snaps: SectionDiff,
+    pub endhost_apis: SectionDiff,
+    pub routers: SectionDiff,
+}
+
+/// The differences of one section. Entries are identified by their key, such as the ISD-AS of
+/// an AS, and described by their details, empty if there are none.
+#[derive(Serialize)]
+pub struct SectionDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub changed: Vec<DiffChange>,
+}
+
+/// An entry that only one of the configs has
+#[derive(Serialize)]
+pub struct DiffEntry {
+    pub key: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub details: String,
+}
+
+/// An entry that both configs have, with different details
+#[derive(Serialize)]
+pub struct DiffChange {
+    pub key: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Compare `old` with `new`. ASes and links are identified by their normalized ISD-AS and link
+/// strings, SNAPs, endhost APIs and routers by their listen address.
+pub fn diff_configs(
+    old: &PocketScionConfig,
+    new: &PocketScionConfig,
+) -> anyhow::Result<ConfigDiff> {
+    Ok(ConfigDiff {
+        ases: diff_section(ases(old)?, ases(new)?),
+        links: diff_section(links(old)?, links(new)?),
+        snaps: diff_section(snaps(old)?, snaps(new)?),
+        endhost_apis: diff_section(endhost_apis(old)?, endhost_apis(new)?),
+        routers: diff_section(routers(old)?, routers(new)?),
+    })
+}
+
+impl ConfigDiff {
+    /// Whether the configs describe the same network
+    pub fn is_empty(&self) -> bool {
+        self.sections()
+            .iter()
+            .all(|(_, section)| section.is_empty())
+    }
+
+    fn sections(&self) -> [(&'static str, &SectionDiff); 5] {
+        [
+            ("ASes", &self.ases),
+            ("Links", &self.links),
+            ("SNAPs", &self.snaps),
+            ("Endhost APIs", &self.endhost_apis),
+            ("Routers", &self.routers),
+        ]
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        let or_none = |details: &str| match details {
+            "" => "none".to_string(),
+            details => details.to_string(),
+        };
+        for (name, section) in self.sections() {
+            if section.is_empty() {
+                continue;
+            }
+            writeln!(f, "{name}:")?;
+            for entry in &section.added {
+                writeln!(f, "  + {entry}")?;
+            }
+            for entry in &section.removed {
+                writeln!(f, "  - {entry}")?;
+            }
+            for change in &section.changed {
+                writeln!(
+                    f,
+                    "  ~ {}: {} -> {}",
+                    change.key,
+                    or_none(&change.old),
+                    or_none(&change.new)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.details.as_str() {
+            "" => write!(f, "{}", self.key),
+            details => write!(f, "{} ({details})", self.key),
+        }
+    }
+}
+
+impl SectionDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The entries of a section by key. A key that a config lists more than once, like a duplicate
+/// AS, is numbered from its second occurrence on, so that no entry is lost.
+fn by_key(entries: Vec<(String, String)>) -> BTreeMap<String, String> {
+    let mut occurrences: BTreeMap<String, usize> = BTreeMap::new();
+    entries
+        .into_iter()
+        .map(|(key, details)| {
+            let occurrence = occurrences.entry(key.clone()).or_default();
+            *occurrence += 1;
+            match *occurrence {
+                1 => (key, details),
+                n => (format!("{key} [{n}]"), details),
+            }
+        })
+        .collect()
+}
+
+/// Compare the entries of a section, given as key and details
+fn diff_section(old: Vec<(String, String)>, new: Vec<(String, String)>) -> SectionDiff {
+    let (old, new) = (by_key(old), by_key(new));
+    let entry = |key: &str, details: &str| DiffEntry {
+        key: key.to_string(),
+        details: details.to_string(),
+    };
+
+    let mut section = SectionDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+    for (key, details) in &new {
+        match old.get(key) {
+            None => section.added.push(entry(key, details)),
+            Some(old_details) if old_details != details => section.changed.push(DiffChange {
+                key: key.clone(),
+                old: old_details.clone(),
+                new: details.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, details) in &old {
+        if !new.contains_key(key) {
+            section.removed.push(entry(key, details));
+        }
+    }
+    section
+}
+
+fn ases(config: &PocketScionConfig) -> anyhow::Result<Vec<(String, String)>> {
+    config
+        .topology
+        .ases
+        .iter()
+        .enumerate()
+        .map(|(index, as_config)| {
+            let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+            let mut details = if as_config.is_core {
+                "core".to_string()
+            } else {
+                "non-core".to_string()
+            };
+            if let Some(mtu) = as_config.default_mtu {
+                details.push_str(&format!(", default MTU {mtu}"));
+            }
+            if let Some(bandwidth_bps) = as_config.default_bandwidth_bps {
+                details.push_str(&format!(", default {bandwidth_bps} bit/s"));
+            }
+            Ok((isd_as.to_string(), details))
+        })
+        .collect()
+}
+
+fn links(config: &PocketScionConfig) -> anyhow::Result<Vec<(String, String)>> {
+    let attributes = link_attributes(&config.topology);
+    config
+        .topology
+        .links
+        .iter()
+        .zip(&attributes)
+        .map(|(link_config, attributes)| {
+            let link_str = link_config.link();
+            let link: ScionLink = link_str
+                .parse()
+                .context(format!("Invalid link '{link_str}'"))?;
+            let attributes = attributes
+                .as_ref()
+                .and_then(attributes_label)
+                .unwrap_or_default();
+            Ok((link.to_string(), attributes))
+        })
+        .collect()
+}
+
+fn snaps(config: &PocketScionConfig) -> anyhow::Result<Vec<(String, String)>> {
+    config
+        .enabled_snaps()
+        .map(|(index, snap)| {
+            let isd_as = parse_isd_as(
+                &snap.data_plane.isd_as,
+                &format!("snaps[{index}].data_plane"),
+            )?;
+            Ok((
+                snap.listening_addr.to_string(),
+                format!(
+                    "data plane of {isd_as} at {}",
+                    snap.data_plane.listening_addr
+                ),
+            ))
+        })
+        .collect()
+}
+
+fn endhost_apis(config: &PocketScionConfig) -> anyhow::Result<Vec<(String, String)>> {
+    config
+        .enabled_endhost_apis()
+        .map(|(index, api)| {
+            let mut isd_ases = api
+                .isds
+                .iter()
+                .map(|isd_as| {
+                    parse_isd_as(isd_as, &format!("endhost_apis[{index}]"))
+                        .map(|isd_as| isd_as.to_string())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            isd_ases.sort();
+            Ok((
+                api.listening_addr.to_string(),
+                format!("serving {}", isd_ases.join(", ")),
+            ))
+        })
+        .collect()
+}
+
+fn routers(config: &PocketScionConfig) -> anyhow::Result<Vec<(String, String)>> {
+    config
+        .enabled_routers()
+        .map(|(index, router)| {
+            let isd_as = parse_isd_as(&router.isd_as, &format!("routers[{index}]"))?;
+            let mut interfaces = router.interfaces.clone();
+            interfaces.sort();
+            let interfaces: Vec<String> = interfaces.iter().map(u16::to_string).collect();
+
+            let mut details = format!("AS {isd_as}, interfaces {}", interfaces.join(", "));
+            if !router.snap_data_plane_excludes.is_empty() {
+                let mut excludes: Vec<String> = router
+                    .snap_data_plane_excludes
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                excludes.sort();
+                details.push_str(&format!(", excludes {}", excludes.join(", ")));
+            }
+            for (interface, addr) in &router.snap_data_plane_interfaces {
+                details.push_str(&format!(", SNAP data plane {interface} at {addr}"));
+            }
+            Ok((router.listening_addr.to_string(), details))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_keys_are_kept() {
+        let entry = |key: &str, details: &str| (key.to_string(), details.to_string());
+        let section = diff_section(
+            vec![entry("1-1", "core"), entry("1-2", "non-core")],
+            vec![
+                entry("1-1", "core"),
+                entry("1-2", "non-core"),
+                entry("1-2", "core"),
+            ],
+        );
+
+        assert_eq!(section.added.len(), 1);
+        assert_eq!(section.added[0].to_string(), "1-2 [2] (core)");
+        assert!(section.removed.is_empty() && section.changed.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Export of the topology as a Graphviz DOT graph
+
+use std::fmt::Write;
+
+use anyhow::Context;
+use pocketscion::network::scion::topology::{ScionLink, ScionLinkType};
+
+use crate::{DetailedLinkConfig, TopologyConfig, link_attributes, parse_isd_as};
+
+/// Render the ASes and links of `topology` as a Graphviz digraph.
+///
+/// Core ASes are drawn as filled boxes. Parent-child links point from the parent to the child,
+/// core links are bold and peering links dashed. The interface IDs are shown at both ends of an
+/// edge and the attributes of a link, if set or inherited from its ASes, as its label.
+pub fn topology_to_dot(topology: &TopologyConfig) -> anyhow::Result<String> {
+    let mut dot = String::from("digraph topology {\n");
+
+    for (index, as_config) in topology.ases.iter().enumerate() {
+        let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        let style = if as_config.is_core {
+            "shape=box, style=filled, fillcolor=lightblue"
+        } else {
+            "shape=ellipse"
+        };
+        writeln!(dot, "    \"{isd_as}\" [{style}];")?;
+    }
+
+    let attributes = link_attributes(topology);
+    for (link_config, attributes) in topology.links.iter().zip(&attributes) {
+        let link_str = link_config.link();
+        let link: ScionLink = link_str
+            .parse()
+            .context(format!("Invalid link '{link_str}'"))?;
+
+        // The child comes first for parent-child links, the order does not matter otherwise
+        let (to, from) = link.get_up_and_downlink();
+        let (from, to) = (from.to_string(), to.to_string());
+        let (Some((from_as, from_if)), Some((to_as, to_if))) =
+            (from.split_once('#'), to.split_once('#'))
+        else {
+            anyhow::bail!("Invalid link '{link_str}'");
+        };
+
+        let from_isd_as = parse_isd_as(from_as, &format!("link '{link_str}'"))?;
+        let mut style = match link.get_link_type(&from_isd_as) {
+            Some(ScionLinkType::Parent) | Some(ScionLinkType::Child) => String::new(),
+            Some(ScionLinkType::Core) => ", style=bold, dir=none".to_string(),
+            Some(ScionLinkType::Peer) | None => ", style=dashed, dir=none".to_string(),
+        };
+        if let Some(label) = attributes.as_ref().and_then(attributes_label) {
+            write!(style, ", label=\"{label}\"")?;
+        }
+        writeln!(
+            dot,
+            "    \"{from_as}\" -> \"{to_as}\" [taillabel=\"{from_if}\", headlabel=\"{to_if}\"{style}];"
+        )?;
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+/// The edge label showing the attributes of a link, if it has any
+pub(crate) fn attributes_label(attributes: &DetailedLinkConfig) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(latency_ms) = attributes.latency_ms {
+        parts.push(match attributes.jitter_ms {
+            Some(jitter_ms) => format!("{latency_ms}±{jitter_ms} ms"),
+            None => format!("{latency_ms} ms"),
+        });
+    }
+    if let Some(bandwidth_bps) = attributes.bandwidth_bps {
+        parts.push(format!("{bandwidth_bps} bit/s"));
+    }
+    if let Some(mtu) = attributes.mtu {
+        parts.push(format!("MTU {mtu}"));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
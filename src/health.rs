@@ -0,0 +1,61 @@
+//! Health check endpoint for liveness and readiness probes
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use tokio::task::JoinHandle;
+
+/// Whether the pocketscion runtime has started and is not shutting down
+#[derive(Clone, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn set_ready(&self, ready: bool) {
+        self.0.store(ready, Ordering::Relaxed);
+    }
+
+    fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Serve `/healthz` at `addr` until the returned task is aborted, and return the bound address
+/// with the task. It responds with `200 OK` while `readiness` is set and with
+/// `503 Service Unavailable` otherwise.
+pub async fn serve(
+    addr: SocketAddr,
+    readiness: Readiness,
+) -> anyhow::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .context(format!("Failed to bind health listener to {addr}"))?;
+    let addr = listener
+        .local_addr()
+        .context("Failed to get the health listen address")?;
+    tracing::info!(health_listen_addr = %addr, "Serving health checks on /healthz");
+
+    let app = Router::new()
+        .route("/healthz", get(healthz_handler))
+        .with_state(readiness);
+
+    let server = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("Health server failed: {}", e);
+        }
+    });
+    Ok((addr, server))
+}
+
+async fn healthz_handler(State(readiness): State<Readiness>) -> (StatusCode, &'static str) {
+    if readiness.is_ready() {
+        (StatusCode::OK, "OK\n")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "Not ready\n")
+    }
+}
@@ -0,0 +1,234 @@
+//! Import of SCION `topology.json` files, as used by the SCION control services and routers
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context;
+use scion_proto::address::IsdAsn;
+use serde::Deserialize;
+
+use crate::{AsConfig, LinkConfig, TopologyConfig, parse_isd_as};
+
+/// The parts of a SCION `topology.json` that describe the AS and its inter-AS links
+#[derive(Debug, Deserialize)]
+struct ScionTopologyFile {
+    isd_as: String,
+    #[serde(default)]
+    attributes: Vec<String>,
+    #[serde(default)]
+    border_routers: BTreeMap<String, BorderRouter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BorderRouter {
+    #[serde(default)]
+    interfaces: BTreeMap<u16, Interface>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Interface {
+    /// The AS on the other end of the link
+    isd_as: String,
+    /// The relation of the other AS to this one: `core`, `parent`, `child` or `peer`
+    link_to: String,
+    /// The interface ID on the other end, only present in newer SCION versions
+    remote_interface_id: Option<u16>,
+}
+
+/// An AS read from a SCION topology file
+struct ImportedAs {
+    isd_as: IsdAsn,
+    is_core: bool,
+    /// Interface ID to remote AS, relation and remote interface ID
+    interfaces: BTreeMap<u16, (IsdAsn, LinkTo, Option<u16>)>,
+}
+
+/// The relation of the AS on the other end of a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkTo {
+    Core,
+    Parent,
+    Child,
+    Peer,
+}
+
+impl LinkTo {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "core" => Ok(Self::Core),
+            "parent" => Ok(Self::Parent),
+            "child" => Ok(Self::Child),
+            "peer" => Ok(Self::Peer),
+            _ => anyhow::bail!("unknown link_to '{s}'"),
+        }
+    }
+
+    /// The relation as seen from the other end of the link
+    fn reverse(self) -> Self {
+        match self {
+            Self::Core => Self::Core,
+            Self::Parent => Self::Child,
+            Self::Child => Self::Parent,
+            Self::Peer => Self::Peer,
+        }
+    }
+
+    /// The link type of the `links` format, read from this end of the link
+    fn link_type(self) -> &'static str {
+        match self {
+            Self::Core => "core",
+            Self::Parent => "child_of",
+            Self::Child => "parent_of",
+            Self::Peer => "peer",
+        }
+    }
+}
+
+/// Build the topology section of a config from SCION topology files, each describing one AS.
+///
+/// ASes that are only referenced by links are added as well, as core ASes if the link is a core
+/// link and as non-core ASes otherwise.
+pub fn import_scion_topologies(paths: &[String]) -> anyhow::Result<TopologyConfig> {
+    let mut imported: BTreeMap<IsdAsn, ImportedAs> = BTreeMap::new();
+    for path in paths {
+        let imported_as =
+            read_scion_topology(path).context(format!("Failed to import SCION topology {path}"))?;
+        if imported.contains_key(&imported_as.isd_as) {
+            anyhow::bail!(
+                "AS {} is described by more than one file",
+                imported_as.isd_as
+            );
+        }
+        imported.insert(imported_as.isd_as, imported_as);
+    }
+
+    let mut ases: Vec<AsConfig> = imported
+        .values()
+        .map(|imported_as| AsConfig {
+            isd_as: imported_as.isd_as.to_string(),
+            is_core: imported_as.is_core,
+            default_mtu: None,
+            default_bandwidth_bps: None,
+        })
+        .collect();
+    let mut referenced: BTreeMap<IsdAsn, bool> = BTreeMap::new();
+
+    let mut links = Vec::new();
+    let mut seen_links = BTreeSet::new();
+    for local in imported.values() {
+        for (&if_id, &(remote, link_to, remote_if_id)) in &local.interfaces {
+            let remote_if_id = match remote_if_id {
+                Some(remote_if_id) => remote_if_id,
+                None => find_remote_interface(&imported, local.isd_as, if_id, remote, link_to)?,
+            };
+
+            // Both ends of a link are usually imported, but the link must only be added once
+            let ends = [(local.isd_as, if_id), (remote, remote_if_id)];
+            let key = if ends[0] <= ends[1] {
+                ends
+            } else {
+                [ends[1], ends[0]]
+            };
+            if !seen_links.insert(key) {
+                continue;
+            }
+
+            if !imported.contains_key(&remote) {
+                *referenced.entry(remote).or_default() |= link_to == LinkTo::Core;
+            }
+            links.push(LinkConfig::Plain(format!(
+                "{}#{} {} {}#{}",
+                local.isd_as,
+                if_id,
+                link_to.link_type(),
+                remote,
+                remote_if_id
+            )));
+        }
+    }
+
+    for (isd_as, is_core) in referenced {
+        tracing::warn!(
+            "AS {} is only referenced by links, adding it as a {} AS",
+            isd_as,
+            if is_core { "core" } else { "non-core" }
+        );
+        ases.push(AsConfig {
+            isd_as: isd_as.to_string(),
+            is_core,
+            default_mtu: None,
+            default_bandwidth_bps: None,
+        });
+    }
+
+    Ok(TopologyConfig { ases, links })
+}
+
+fn read_scion_topology(path: &str) -> anyhow::Result<ImportedAs> {
+    let content = std::fs::read_to_string(path).context("Failed to read file")?;
+    let file: ScionTopologyFile =
+        serde_json::from_str(&content).context("Failed to parse file as JSON")?;
+
+    let isd_as = parse_isd_as(&file.isd_as, "isd_as")?;
+    let is_core = file
+        .attributes
+        .iter()
+        .any(|attribute| attribute.eq_ignore_ascii_case("core"));
+
+    let mut interfaces = BTreeMap::new();
+    for (router, border_router) in file.border_routers {
+        for (if_id, interface) in border_router.interfaces {
+            let remote = parse_isd_as(&interface.isd_as, &format!("interface {if_id}"))?;
+            let link_to = LinkTo::parse(&interface.link_to).context(format!(
+                "Invalid interface {if_id} of border router {router}"
+            ))?;
+            if interfaces
+                .insert(if_id, (remote, link_to, interface.remote_interface_id))
+                .is_some()
+            {
+                anyhow::bail!("Interface {if_id} is used by more than one border router");
+            }
+        }
+    }
+
+    Ok(ImportedAs {
+        isd_as,
+        is_core,
+        interfaces,
+    })
+}
+
+/// Find the interface ID on the other end of a link without `remote_interface_id`, which is only
+/// possible if the remote AS is imported as well and has exactly one matching interface
+fn find_remote_interface(
+    imported: &BTreeMap<IsdAsn, ImportedAs>,
+    local: IsdAsn,
+    if_id: u16,
+    remote: IsdAsn,
+    link_to: LinkTo,
+) -> anyhow::Result<u16> {
+    let Some(remote_as) = imported.get(&remote) else {
+        anyhow::bail!(
+            "Interface {if_id} of AS {local} has no remote_interface_id and AS {remote} is not imported"
+        );
+    };
+
+    let mut candidates = remote_as
+        .interfaces
+        .iter()
+        .filter(|(_, (other, other_link_to, other_remote))| {
+            *other == local
+                && *other_link_to == link_to.reverse()
+                && other_remote.is_none_or(|other_remote| other_remote == if_id)
+        })
+        .map(|(&remote_if_id, _)| remote_if_id);
+
+    match (candidates.next(), candidates.next()) {
+        (Some(remote_if_id), None) => Ok(remote_if_id),
+        (None, _) => anyhow::bail!(
+            "Interface {if_id} of AS {local} has no remote_interface_id and AS {remote} has no matching interface"
+        ),
+        (Some(_), Some(_)) => anyhow::bail!(
+            "Interface {if_id} of AS {local} has no remote_interface_id and AS {remote} has more than one matching interface"
+        ),
+    }
+}
@@ -0,0 +1,86 @@
+//! Summary of the ASes of a config and the parts of the network that serve them
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use scion_proto::address::IsdAsn;
+use serde::Serialize;
+
+use crate::{PocketScionConfig, build_topology_from_config, link_ases, parse_isd_as};
+
+/// What the config declares for one AS
+#[derive(Default, Serialize)]
+pub struct AsSummary {
+    pub isd_as: String,
+    pub is_core: bool,
+    /// The number of links the AS is part of
+    pub links: usize,
+    /// The number of SNAP data planes serving the AS
+    pub snaps: usize,
+    pub has_router: bool,
+}
+
+/// A summary of every AS of `config`, ordered by ISD-AS. The topology is built first, so an
+/// invalid topology is reported like on startup.
+pub fn as_summaries(config: &PocketScionConfig) -> anyhow::Result<Vec<AsSummary>> {
+    build_topology_from_config(&config.topology)?;
+
+    let mut ases: BTreeMap<IsdAsn, AsSummary> = BTreeMap::new();
+    for (index, as_config) in config.topology.ases.iter().enumerate() {
+        let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        summary(&mut ases, isd_as).is_core = as_config.is_core;
+    }
+    for link_config in &config.topology.links {
+        for isd_as in link_ases(&link_config.link())? {
+            summary(&mut ases, isd_as).links += 1;
+        }
+    }
+    for (index, snap) in config.enabled_snaps() {
+        let isd_as = parse_isd_as(
+            &snap.data_plane.isd_as,
+            &format!("snaps[{index}].data_plane"),
+        )?;
+        summary(&mut ases, isd_as).snaps += 1;
+    }
+    for (index, router) in config.enabled_routers() {
+        let isd_as = parse_isd_as(&router.isd_as, &format!("routers[{index}]"))?;
+        summary(&mut ases, isd_as).has_router = true;
+    }
+    Ok(ases.into_values().collect())
+}
+
+fn summary(ases: &mut BTreeMap<IsdAsn, AsSummary>, isd_as: IsdAsn) -> &mut AsSummary {
+    ases.entry(isd_as).or_insert_with(|| AsSummary {
+        isd_as: isd_as.to_string(),
+        ..AsSummary::default()
+    })
+}
+
+/// A table with a row for every AS of `config`: whether it is a core AS, the number of links it
+/// is part of, the number of SNAP data planes serving it and whether it has a router
+pub fn as_table(config: &PocketScionConfig) -> anyhow::Result<String> {
+    let ases = as_summaries(config)?;
+
+    let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+    let width = ases
+        .iter()
+        .map(|summary| summary.isd_as.len())
+        .chain(["ISD-AS".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = String::new();
+    writeln!(table, "{:width$}  Core  Links  SNAPs  Router", "ISD-AS")?;
+    for summary in &ases {
+        writeln!(
+            table,
+            "{:width$}  {:4}  {:>5}  {:>5}  {}",
+            summary.isd_as,
+            yes_no(summary.is_core),
+            summary.links,
+            summary.snaps,
+            yes_no(summary.has_router)
+        )?;
+    }
+    Ok(table)
+}
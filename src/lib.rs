@@ -0,0 +1,1181 @@
+//! Configure and run the pocketscion simulator with networks described in JSON or YAML configs.
+//!
+//! The `pocketscion-configurator` binary is a command line wrapper around this library.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::num::NonZeroU16;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use pocketscion::io_config;
+use pocketscion::network::scion::topology::{ScionAs, ScionLink, ScionTopology};
+use pocketscion::runtime::{PocketScionRuntime, PocketScionRuntimeBuilder};
+use pocketscion::state::SharedPocketScionState;
+use pocketscion::state::snap::SnapId;
+use scion_proto::address::IsdAsn;
+
+mod builder;
+mod config;
+pub mod control;
+pub mod diff;
+pub mod dot;
+pub mod health;
+pub mod import;
+pub mod inspect;
+pub mod manifest;
+pub mod metrics;
+pub mod ports;
+pub mod reachability;
+pub mod scale;
+pub mod schema;
+pub mod tokens;
+
+pub use builder::ConfigBuilder;
+pub use config::{
+    AsConfig, ConfigFormat, DataPlaneConfig, DetailedLinkConfig, EndhostApiConfig, LinkConfig,
+    LinkRelationship, LoadOptions, LoggingConfig, PocketScionConfig, RouterConfig, SnapConfig,
+    TopologyConfig, UnknownFields, load_configs, load_vars, unwatchable_source,
+};
+
+/// How warnings about a config, such as router interfaces that no link uses, are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigWarnings {
+    /// Warnings are logged and the config is used anyway
+    #[default]
+    Log,
+    /// The first warning is returned as an error, for CI
+    Deny,
+}
+
+impl ConfigWarnings {
+    /// Log `message` as a warning, or return it as an error with [`ConfigWarnings::Deny`]
+    fn report(self, message: String) -> anyhow::Result<()> {
+        match self {
+            ConfigWarnings::Log => {
+                tracing::warn!("{}", message);
+                Ok(())
+            }
+            ConfigWarnings::Deny => Err(anyhow::anyhow!(message)),
+        }
+    }
+}
+
+/// The errors found while validating a config. With fail-fast, the first error is returned right
+/// away, otherwise all errors are collected and reported together by [`ValidationErrors::finish`].
+struct ValidationErrors {
+    fail_fast: bool,
+    errors: Vec<anyhow::Error>,
+}
+
+impl ValidationErrors {
+    fn new(fail_fast: bool) -> Self {
+        Self {
+            fail_fast,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Return the value of a check, or `None` if it failed and its error was collected
+    fn check<T, E: Into<anyhow::Error>>(
+        &mut self,
+        result: Result<T, E>,
+    ) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => self.report(e.into()).map(|()| None),
+        }
+    }
+
+    /// Return `error` with fail-fast, or collect it
+    fn report(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        if self.fail_fast {
+            return Err(error);
+        }
+        self.errors.push(error);
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Report the collected errors as a single error, with a line for each
+    fn finish(self) -> anyhow::Result<()> {
+        let mut errors = self.errors;
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            count => {
+                let lines: Vec<String> = errors
+                    .iter()
+                    .map(|e| format!("  - {}", format!("{e:#}").replace('\n', "\n    ")))
+                    .collect();
+                anyhow::bail!("Found {count} errors in the config:\n{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+/// The SNAP ID pocketscion gave each enabled SNAP, with the index of the SNAP in `snaps` of the
+/// config
+pub type SnapIds = Vec<(usize, SnapId)>;
+
+/// Build the topology, system state and IO config the runtime is started with, and the IDs of
+/// the SNAPs in it
+pub fn build_runtime_state(
+    config: &PocketScionConfig,
+    warnings: ConfigWarnings,
+) -> anyhow::Result<(
+    SharedPocketScionState,
+    io_config::SharedPocketScionIoConfig,
+    SnapIds,
+)> {
+    check_runtime_state(config, warnings, &mut ValidationErrors::new(true))
+}
+
+/// Check that the runtime state can be built from `config`, like [`build_runtime_state`].
+/// Without `fail_fast`, all errors are collected and returned as a single error with a line for
+/// each, such as duplicate ASes, links to undeclared ASes, invalid ISD-ASes, invalid router
+/// interfaces and conflicting listen addresses. Checks that depend on a failed one are skipped.
+pub fn validate_config(
+    config: &PocketScionConfig,
+    warnings: ConfigWarnings,
+    fail_fast: bool,
+) -> anyhow::Result<()> {
+    let mut errors = ValidationErrors::new(fail_fast);
+    check_runtime_state(config, warnings, &mut errors)?;
+    errors.finish()
+}
+
+/// Build the runtime state like [`build_runtime_state`], recording the errors in `errors`
+fn check_runtime_state(
+    config: &PocketScionConfig,
+    warnings: ConfigWarnings,
+    errors: &mut ValidationErrors,
+) -> anyhow::Result<(
+    SharedPocketScionState,
+    io_config::SharedPocketScionIoConfig,
+    SnapIds,
+)> {
+    errors.check(ports::check_conflicts(config))?;
+    if config.control_auth_token.as_deref() == Some("") {
+        errors.report(anyhow::anyhow!(
+            "control_auth_token is empty, remove it or set a token"
+        ))?;
+    }
+    // The token does not protect the management API, which could be used to bypass it
+    if let (Some(_), Some(addr)) = (&config.control_auth_token, config.management_listen_addr)
+        && !addr.ip().is_loopback()
+    {
+        errors.report(anyhow::anyhow!(
+            "management_listen_addr {addr} must be a loopback address if control_auth_token is \
+             set, as the management API does not check the token"
+        ))?;
+    }
+    if let Some(logging) = &config.logging {
+        errors.check(logging.env_filter())?;
+    }
+
+    // Build topology from config
+    let topology = build_topology(&config.topology, errors)?;
+
+    // Build the system state and IO config from config
+    build_state_from_config(config, topology, warnings, errors)
+}
+
+/// The management API listen address if the config does not set one
+pub const DEFAULT_MANAGEMENT_LISTEN_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+/// Start the simulator with the network of `config`, like the command line tool does without
+/// its servers and token files. Listen addresses with port 0 get a free port, which the
+/// management API reports for the SNAPs, endhost APIs and routers. SNAPs accept the dummy token of
+/// [`snap_tokens::v0::dummy_snap_token`].
+pub async fn start_runtime(config: &PocketScionConfig) -> anyhow::Result<PocketScionRuntime> {
+    let mut config = config.clone();
+    ports::resolve_ephemeral_ports(&mut config)?;
+    run_from_config(&config, |_| {}).await
+}
+
+/// Start the pocketscion runtime with the given system state and IO config
+pub async fn start_runtime_with_state(
+    config: &PocketScionConfig,
+    system_state: SharedPocketScionState,
+    io_config: io_config::SharedPocketScionIoConfig,
+) -> anyhow::Result<PocketScionRuntime> {
+    let management_listen_addr = config
+        .management_listen_addr
+        .unwrap_or(DEFAULT_MANAGEMENT_LISTEN_ADDR);
+    tracing::info!(
+        %management_listen_addr,
+        "Starting Pocket SCION runtime..."
+    );
+
+    // Finally we create the PocketScionRuntime
+    let rt: PocketScionRuntime = PocketScionRuntimeBuilder::new()
+        .with_system_state(system_state.into_state())
+        .with_io_config(io_config.into_state())
+        .with_mgmt_listen_addr(management_listen_addr)
+        .start()
+        .await
+        .context("error starting Pocket SCION runtime")?;
+
+    tracing::info!(%management_listen_addr, "Pocket SCION runtime started");
+
+    Ok(rt)
+}
+
+/// Start the simulator like [`start_runtime_with_state`], and try again up to `retries` times,
+/// `delay` apart, while a listen address is still in use, for example by a socket of a previous
+/// run in TIME_WAIT. Other errors are returned right away.
+pub async fn start_runtime_with_retries(
+    config: &PocketScionConfig,
+    system_state: SharedPocketScionState,
+    io_config: io_config::SharedPocketScionIoConfig,
+    retries: u32,
+    delay: Duration,
+) -> anyhow::Result<PocketScionRuntime> {
+    let system_state = system_state.into_state();
+    let io_config = io_config.into_state();
+
+    let mut attempt = 0;
+    loop {
+        let result = start_runtime_with_state(
+            config,
+            SharedPocketScionState::from_system_state(system_state.clone()),
+            io_config::SharedPocketScionIoConfig::from_state(io_config.clone()),
+        )
+        .await;
+        match result {
+            Err(e) if attempt < retries && is_addr_in_use(&e) => {
+                attempt += 1;
+                tracing::warn!(
+                    "Failed to start Pocket SCION runtime: {:#}, retrying in {}ms ({} of {})",
+                    e,
+                    delay.as_millis(),
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Log which SNAP of `config` has which ID, as pocketscion logs its SNAPs by ID. The data plane
+/// of a SNAP has the ID of the SNAP.
+pub fn log_snap_ids(config: &PocketScionConfig, snap_ids: &SnapIds) {
+    let snaps = config.snaps.as_deref().unwrap_or_default();
+    for &(index, snap_id) in snap_ids {
+        let snap = &snaps[index];
+        tracing::info!(
+            %snap_id,
+            isd_as = %snap.data_plane.isd_as,
+            control_plane_addr = %snap.listening_addr,
+            data_plane_addr = %snap.data_plane.listening_addr,
+            "snaps[{}] of the config is SNAP {}",
+            index,
+            snap_id
+        );
+    }
+}
+
+/// Whether `error` was caused by binding an address that is in use
+fn is_addr_in_use(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::AddrInUse)
+    })
+}
+
+/// Build the runtime state of `config` and start the simulator with it, without any of the
+/// servers and token files of the command line tool. `io_overrides` can change the IO config
+/// before the start, for example to move a listener. SNAPs accept the dummy token of
+/// [`snap_tokens::v0::dummy_snap_token`].
+pub async fn run_from_config(
+    config: &PocketScionConfig,
+    io_overrides: impl FnOnce(&io_config::SharedPocketScionIoConfig),
+) -> anyhow::Result<PocketScionRuntime> {
+    let (system_state, io_config, snap_ids) = build_runtime_state(config, ConfigWarnings::Log)?;
+    io_overrides(&io_config);
+    let runtime = start_runtime_with_state(config, system_state, io_config).await?;
+    log_snap_ids(config, &snap_ids);
+    Ok(runtime)
+}
+
+/// Stop the runtime and wait at most `timeout` for all of its tasks to finish
+pub async fn shutdown_runtime(runtime: &mut PocketScionRuntime, timeout: Duration) {
+    match tokio::time::timeout(timeout, runtime.stop_and_join()).await {
+        Ok(()) => tracing::info!("Pocket SCION runtime stopped"),
+        Err(_) => {
+            tracing::warn!(
+                "Pocket SCION runtime did not stop within {}s, aborting its remaining tasks",
+                timeout.as_secs()
+            )
+        }
+    }
+}
+
+/// Build the system state and IO config for the runtime from the config structure, recording
+/// the errors in `errors`
+fn build_state_from_config(
+    config: &PocketScionConfig,
+    topology: ScionTopology,
+    warnings: ConfigWarnings,
+    errors: &mut ValidationErrors,
+) -> anyhow::Result<(
+    SharedPocketScionState,
+    io_config::SharedPocketScionIoConfig,
+    SnapIds,
+)> {
+    let mut system_state = SharedPocketScionState::new(SystemTime::now());
+    let io_config = io_config::SharedPocketScionIoConfig::new();
+
+    // The SNAPs, endhost APIs and routers can only be added to ASes of the topology
+    if !check_referenced_ases(config, errors)? {
+        // Only the router interfaces can be checked without them
+        for (index, router_config) in config.enabled_routers() {
+            router_interfaces(router_config, &format!("routers[{index}]"), errors)?;
+        }
+        return Ok((system_state, io_config, SnapIds::new()));
+    }
+    // The warnings would repeat the errors of an invalid config
+    if errors.is_empty() {
+        errors.check(warn_unsimulated_link_attributes(&config.topology, warnings))?;
+        if config.endhost_apis.is_some() {
+            errors.check(warn_endhost_api_coverage(config, warnings))?;
+        }
+        errors.check(warn_orphaned_router_interfaces(config, &topology, warnings))?;
+    }
+
+    // Set the topology
+    system_state.set_topology(topology);
+
+    // Create SCION Network Access Points (SNAPs) if present
+    let mut snap_ids = SnapIds::new();
+    for (index, snap) in config.enabled_snaps() {
+        let isd_as = parse_isd_as(
+            &snap.data_plane.isd_as,
+            &format!("snaps[{index}].data_plane"),
+        )?;
+
+        // Add a new SNAP to the system state
+        let Some(snap_id) = errors.check(system_state.add_snap(isd_as))? else {
+            continue;
+        };
+        snap_ids.push((index, snap_id));
+
+        // Then add an IO config to declare how this control plane can be reached
+        io_config.set_snap_control_addr(snap_id, snap.listening_addr);
+
+        // Add an IO config
+        io_config.set_snap_data_plane_addr(snap_id, snap.data_plane.listening_addr);
+    }
+
+    // Configure endhost APIs if present
+    for (index, api_config) in config.enabled_endhost_apis() {
+        let isds: Vec<IsdAsn> = api_config
+            .isds
+            .iter()
+            .map(|s| parse_isd_as(s, &format!("endhost_apis[{index}].isds")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let endhost_api_id = system_state.add_endhost_api(isds);
+        io_config.set_endhost_api_addr(endhost_api_id, api_config.listening_addr);
+    }
+
+    // Configure routers if present
+    for (index, router_config) in config.enabled_routers() {
+        let isd_as = parse_isd_as(&router_config.isd_as, &format!("routers[{index}]"))?;
+        let Some(interfaces) =
+            router_interfaces(router_config, &format!("routers[{index}]"), errors)?
+        else {
+            continue;
+        };
+
+        let router_id = system_state.add_router(
+            isd_as,
+            interfaces,
+            router_config.snap_data_plane_excludes.clone(),
+            router_config.snap_data_plane_interfaces.clone(),
+        );
+        io_config.set_router_socket_addr(router_id, router_config.listening_addr);
+    }
+
+    Ok((system_state, io_config, snap_ids))
+}
+
+/// The interface IDs of a router, which must be non-zero and unique. Every invalid ID is
+/// reported to `errors`, naming the router with `context`, and there are no IDs if any is invalid.
+fn router_interfaces(
+    router_config: &RouterConfig,
+    context: &str,
+    errors: &mut ValidationErrors,
+) -> anyhow::Result<Option<Vec<NonZeroU16>>> {
+    let mut seen = BTreeSet::new();
+    let mut duplicates = BTreeSet::new();
+    let mut interfaces = Vec::with_capacity(router_config.interfaces.len());
+    let mut valid = true;
+    for (position, &if_id) in router_config.interfaces.iter().enumerate() {
+        let isd_as = &router_config.isd_as;
+        let Some(if_id) = NonZeroU16::new(if_id) else {
+            errors.report(anyhow::anyhow!(
+                "Interface ID 0 in {context}.interfaces[{position}] of AS {isd_as} must be non-zero"
+            ))?;
+            valid = false;
+            continue;
+        };
+        if !seen.insert(if_id) {
+            if duplicates.insert(if_id) {
+                errors.report(anyhow::anyhow!(
+                    "Interface ID {if_id} is listed more than once in {context}.interfaces of AS {isd_as}"
+                ))?;
+            }
+            valid = false;
+            continue;
+        }
+        interfaces.push(if_id);
+    }
+    Ok(valid.then_some(interfaces))
+}
+
+/// Check that the SNAP data planes, endhost APIs and routers only refer to ASes declared in the
+/// topology, reporting all entries that do not. Returns whether they all do.
+fn check_referenced_ases(
+    config: &PocketScionConfig,
+    errors: &mut ValidationErrors,
+) -> anyhow::Result<bool> {
+    // ASes with an invalid ISD-AS are reported with the topology
+    let declared: BTreeSet<IsdAsn> = config
+        .topology
+        .ases
+        .iter()
+        .filter_map(|as_config| as_config.isd_as.parse().ok())
+        .collect();
+
+    let mut referenced = Vec::new();
+    for (index, snap) in config.enabled_snaps() {
+        referenced.push((
+            format!("snaps[{index}].data_plane"),
+            &snap.data_plane.isd_as,
+        ));
+    }
+    for (index, api_config) in config.enabled_endhost_apis() {
+        for isd_as in &api_config.isds {
+            referenced.push((format!("endhost_apis[{index}].isds"), isd_as));
+        }
+    }
+    for (index, router_config) in config.enabled_routers() {
+        referenced.push((format!("routers[{index}]"), &router_config.isd_as));
+    }
+
+    let mut valid = true;
+    let mut undeclared = Vec::new();
+    for (context, isd_as) in referenced {
+        match errors.check(parse_isd_as(isd_as, &context))? {
+            Some(parsed) if declared.contains(&parsed) => {}
+            Some(_) => undeclared.push(format!("  {context}: {isd_as}")),
+            None => valid = false,
+        }
+    }
+    if !undeclared.is_empty() {
+        errors.report(anyhow::anyhow!(
+            "Config refers to ASes that are not declared in the topology:\n{}",
+            undeclared.join("\n")
+        ))?;
+        valid = false;
+    }
+    Ok(valid)
+}
+
+/// Warn about ISDs of the topology in which no endhost API serves any AS, and about ISDs that
+/// more than one endhost API serves. pocketscion runs every endhost API on its own, so both are
+/// allowed but rarely intended.
+fn warn_endhost_api_coverage(
+    config: &PocketScionConfig,
+    warnings: ConfigWarnings,
+) -> anyhow::Result<()> {
+    let mut unserved_isds = BTreeSet::new();
+    for (index, as_config) in config.topology.ases.iter().enumerate() {
+        let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        unserved_isds.insert(isd_as.isd().0);
+    }
+
+    let mut served_by: BTreeMap<u16, BTreeSet<SocketAddr>> = BTreeMap::new();
+    for (index, api_config) in config.enabled_endhost_apis() {
+        for isd_as in &api_config.isds {
+            let isd_as = parse_isd_as(isd_as, &format!("endhost_apis[{index}].isds"))?;
+            unserved_isds.remove(&isd_as.isd().0);
+            served_by
+                .entry(isd_as.isd().0)
+                .or_default()
+                .insert(api_config.listening_addr);
+        }
+    }
+
+    if !unserved_isds.is_empty() {
+        let isds: Vec<String> = unserved_isds.iter().map(|isd| isd.to_string()).collect();
+        warnings.report(format!(
+            "No endhost API serves an AS of ISD {}",
+            isds.join(", ")
+        ))?;
+    }
+    for (isd, apis) in served_by.iter().filter(|(_, apis)| apis.len() > 1) {
+        let apis: Vec<String> = apis.iter().map(|addr| addr.to_string()).collect();
+        warnings.report(format!(
+            "ISD {isd} is served by more than one endhost API: {}",
+            apis.join(", ")
+        ))?;
+    }
+    Ok(())
+}
+
+/// Warn about router interfaces that are not used by any link of the router's AS, which is
+/// almost always a mistake in the config
+fn warn_orphaned_router_interfaces(
+    config: &PocketScionConfig,
+    topology: &ScionTopology,
+    warnings: ConfigWarnings,
+) -> anyhow::Result<()> {
+    for (index, router_config) in config.enabled_routers() {
+        let context = format!("routers[{index}]");
+        let isd_as = parse_isd_as(&router_config.isd_as, &context)?;
+        // Invalid interfaces are reported when the router is added
+        let Ok(Some(interfaces)) =
+            router_interfaces(router_config, &context, &mut ValidationErrors::new(true))
+        else {
+            continue;
+        };
+        let orphaned: Vec<String> = interfaces
+            .into_iter()
+            .filter(|if_id| topology.scion_link(&isd_as, if_id.get()).is_none())
+            .map(|if_id| if_id.to_string())
+            .collect();
+        if !orphaned.is_empty() {
+            warnings.report(format!(
+                "Router of AS {isd_as} has interfaces that no link uses: {}",
+                orphaned.join(", ")
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Limits on the size of a topology, to stop a runaway generated config before it is built.
+/// Unlimited by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TopologyLimits {
+    /// The most ASes the topology may have
+    pub max_ases: Option<usize>,
+    /// The most links the topology may have
+    pub max_links: Option<usize>,
+}
+
+impl TopologyLimits {
+    /// Check that `topology` is within the limits
+    pub fn check(&self, topology: &TopologyConfig) -> anyhow::Result<()> {
+        if let Some(max_ases) = self.max_ases
+            && topology.ases.len() > max_ases
+        {
+            anyhow::bail!(
+                "Topology has {} ASes, more than the maximum of {max_ases}",
+                topology.ases.len()
+            );
+        }
+        if let Some(max_links) = self.max_links
+            && topology.links.len() > max_links
+        {
+            anyhow::bail!(
+                "Topology has {} links, more than the maximum of {max_links}",
+                topology.links.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Build a topology from the config structure
+pub fn build_topology_from_config(config: &TopologyConfig) -> anyhow::Result<ScionTopology> {
+    build_topology(config, &mut ValidationErrors::new(true))
+}
+
+/// Build a topology from the config structure, recording its errors in `errors`. Without
+/// fail-fast, the topology only has the ASes and links without errors.
+fn build_topology(
+    config: &TopologyConfig,
+    errors: &mut ValidationErrors,
+) -> anyhow::Result<ScionTopology> {
+    let mut topo = ScionTopology::new();
+
+    // Add all ASes
+    let mut declared_ases = BTreeMap::new();
+    let mut ases_valid = true;
+    for (index, as_config) in config.ases.iter().enumerate() {
+        let Some(isd_asn) = errors.check(parse_isd_as(
+            &as_config.isd_as,
+            &format!("topology.ases[{index}]"),
+        ))?
+        else {
+            ases_valid = false;
+            continue;
+        };
+        if let Some(first) = declared_ases.insert(isd_asn, index) {
+            // The first declaration is the one that is built
+            declared_ases.insert(isd_asn, first);
+            errors.report(anyhow::anyhow!(
+                "duplicate AS {isd_asn} declared in topology at topology.ases[{first}] and topology.ases[{index}]"
+            ))?;
+            continue;
+        }
+        errors.check(check_as_link_defaults(as_config))?;
+        let scion_as = if as_config.is_core {
+            ScionAs::new_core(isd_asn)
+        } else {
+            ScionAs::new(isd_asn)
+        };
+        errors.check(topo.add_as(scion_as).map(drop))?;
+    }
+    // The ISDs of ASes with an invalid ISD-AS are unknown
+    if ases_valid {
+        errors.check(check_core_ases(config))?;
+    }
+    // The links that can not be parsed are only reported here
+    errors.check(check_link_syntax(config))?;
+    errors.check(check_duplicate_links(config))?;
+    let attributes = link_attributes(config);
+
+    // Add all links, making sure both ends refer to declared ASes
+    for (link_config, attributes) in config.links.iter().zip(&attributes) {
+        let link_str = link_config.link();
+        let Ok(link) = link_str.parse::<ScionLink>() else {
+            continue;
+        };
+        let mut dangling = false;
+        for isd_as in link_ases(&link_str)? {
+            if !declared_ases.contains_key(&isd_as) {
+                errors.report(anyhow::anyhow!(
+                    "Link '{link_str}' references AS {isd_as}, which is not declared in the topology"
+                ))?;
+                dangling = true;
+            }
+        }
+        if let Some(attributes) = attributes {
+            errors.check(check_link_attributes(attributes))?;
+        }
+        if !dangling {
+            errors.check(
+                topo.add_link(link)
+                    .map(drop)
+                    .with_context(|| format!("Invalid link '{link_str}'")),
+            )?;
+        }
+    }
+
+    Ok(topo)
+}
+
+/// The attributes of every link, with the MTU and bandwidth the link inherits from its ASes if
+/// it does not set them. A link inherits the smaller of the defaults of its two ASes, or the
+/// default of one AS if only that one sets it. Links that neither set nor inherit any attribute
+/// have none. Links that can not be parsed inherit nothing.
+pub fn link_attributes(config: &TopologyConfig) -> Vec<Option<DetailedLinkConfig>> {
+    // An AS with an invalid ISD-AS is reported by the validation, its defaults are ignored here
+    let ases: HashMap<IsdAsn, &AsConfig> = config
+        .ases
+        .iter()
+        .filter_map(|as_config| Some((as_config.isd_as.parse().ok()?, as_config)))
+        .collect();
+
+    config
+        .links
+        .iter()
+        .map(|link_config| {
+            let ends: Vec<&AsConfig> = link_ases(&link_config.link())
+                .into_iter()
+                .flatten()
+                .filter_map(|isd_as| ases.get(&isd_as).copied())
+                .collect();
+            let mtu = ends
+                .iter()
+                .filter_map(|as_config| as_config.default_mtu)
+                .min();
+            let bandwidth_bps = ends
+                .iter()
+                .filter_map(|as_config| as_config.default_bandwidth_bps)
+                .min();
+
+            let mut attributes = match link_config {
+                LinkConfig::Detailed(detailed) => detailed.clone(),
+                LinkConfig::Plain(_) if mtu.is_none() && bandwidth_bps.is_none() => {
+                    return None;
+                }
+                LinkConfig::Plain(link) => DetailedLinkConfig {
+                    link: link.clone(),
+                    relationship: None,
+                    latency_ms: None,
+                    jitter_ms: None,
+                    bandwidth_bps: None,
+                    mtu: None,
+                },
+            };
+            attributes.mtu = attributes.mtu.or(mtu);
+            attributes.bandwidth_bps = attributes.bandwidth_bps.or(bandwidth_bps);
+            Some(attributes)
+        })
+        .collect()
+}
+
+/// Check the link defaults of an AS, which hold for links that inherit them
+fn check_as_link_defaults(as_config: &AsConfig) -> anyhow::Result<()> {
+    let isd_as = &as_config.isd_as;
+    if as_config.default_bandwidth_bps == Some(0) {
+        anyhow::bail!("AS {isd_as} has a default_bandwidth_bps of 0, it must be positive");
+    }
+    if let Some(mtu) = as_config.default_mtu.filter(|&mtu| mtu < MIN_LINK_MTU) {
+        anyhow::bail!("AS {isd_as} has a default_mtu of {mtu}, it must be at least {MIN_LINK_MTU}");
+    }
+    Ok(())
+}
+
+/// Report links that were declared more than once, also in the other direction. A link is used
+/// in both directions, so declaring the reverse of a link is a mistake.
+fn check_duplicate_links(config: &TopologyConfig) -> anyhow::Result<()> {
+    let mut declared: HashMap<ScionLink, (usize, String)> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (index, link_config) in config.links.iter().enumerate() {
+        let link_str = link_config.link();
+        // Links that can not be parsed are reported by check_link_syntax
+        let Ok(link) = link_str.parse::<ScionLink>() else {
+            continue;
+        };
+        match declared.get(&link) {
+            Some((first_index, first_str)) => duplicates.push(format!(
+                "  topology.links[{index}] '{link_str}' is the same link as topology.links[{first_index}] '{first_str}'"
+            )),
+            None => {
+                declared.insert(link, (index, link_str.into_owned()));
+            }
+        }
+    }
+
+    if !duplicates.is_empty() {
+        anyhow::bail!(
+            "Links declared more than once, every link is used in both directions:\n{}",
+            duplicates.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Parse every link, and report all links that can not be parsed in a single error
+fn check_link_syntax(config: &TopologyConfig) -> anyhow::Result<()> {
+    let invalid: Vec<String> = config
+        .links
+        .iter()
+        .enumerate()
+        .filter_map(|(index, link_config)| {
+            let link_str = link_config.link();
+            link_str
+                .parse::<ScionLink>()
+                .err()
+                .map(|e| format!("  topology.links[{index}] '{link_str}': {e}"))
+        })
+        .collect();
+
+    if !invalid.is_empty() {
+        anyhow::bail!("Invalid links:\n{}", invalid.join("\n"));
+    }
+    Ok(())
+}
+
+/// Check that every ISD has at least one core AS, without which no beacons are originated and no
+/// paths are found
+fn check_core_ases(config: &TopologyConfig) -> anyhow::Result<()> {
+    let mut has_core: BTreeMap<u16, bool> = BTreeMap::new();
+    for (index, as_config) in config.ases.iter().enumerate() {
+        let isd_asn = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        *has_core.entry(isd_asn.isd().0).or_default() |= as_config.is_core;
+    }
+
+    let without_core: Vec<String> = has_core
+        .into_iter()
+        .filter(|(_, has_core)| !has_core)
+        .map(|(isd, _)| isd.to_string())
+        .collect();
+    if !without_core.is_empty() {
+        anyhow::bail!(
+            "No core AS in ISD {}, every ISD needs at least one AS with is_core set",
+            without_core.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Check the attributes of a link. pocketscion 0.4 does not simulate any link properties and
+/// uses an MTU of 1280 for every link, so they are only checked and shown in the DOT export,
+/// see [`warn_unsimulated_link_attributes`].
+fn check_link_attributes(attributes: &DetailedLinkConfig) -> anyhow::Result<()> {
+    let link_str = &attributes.link;
+    if let Some(jitter_ms) = attributes.jitter_ms {
+        match attributes.latency_ms {
+            None => anyhow::bail!("Link '{link_str}' sets jitter_ms without latency_ms"),
+            Some(latency_ms) if jitter_ms > latency_ms => {
+                anyhow::bail!("Link '{link_str}' has a jitter_ms larger than its latency_ms")
+            }
+            Some(_) => {}
+        }
+    }
+    if attributes.bandwidth_bps == Some(0) {
+        anyhow::bail!("Link '{link_str}' has a bandwidth_bps of 0, it must be positive");
+    }
+    if let Some(mtu) = attributes.mtu.filter(|&mtu| mtu < MIN_LINK_MTU) {
+        anyhow::bail!("Link '{link_str}' has an mtu of {mtu}, it must be at least {MIN_LINK_MTU}");
+    }
+    if let (Some(relationship), [_, link_type, _]) = (
+        attributes.relationship,
+        link_str.split_whitespace().collect::<Vec<_>>().as_slice(),
+    ) && LinkRelationship::of_link_type(link_type) != Some(relationship)
+    {
+        anyhow::bail!(
+            "Link '{link_str}' is a {link_type} link, but its relationship is {relationship}"
+        );
+    }
+
+    Ok(())
+}
+
+/// Warn about link attributes, which pocketscion 0.4 does not simulate
+fn warn_unsimulated_link_attributes(
+    topology: &TopologyConfig,
+    warnings: ConfigWarnings,
+) -> anyhow::Result<()> {
+    for as_config in &topology.ases {
+        let ignored: Vec<&str> = [
+            ("default_mtu", as_config.default_mtu.is_some()),
+            (
+                "default_bandwidth_bps",
+                as_config.default_bandwidth_bps.is_some(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        if !ignored.is_empty() {
+            warnings.report(format!(
+                "AS {} sets {}, which pocketscion 0.4 does not simulate",
+                as_config.isd_as,
+                ignored.join(", ")
+            ))?;
+        }
+    }
+    for attributes in topology.links.iter().filter_map(LinkConfig::attributes) {
+        let ignored: Vec<&str> = [
+            ("latency_ms", attributes.latency_ms.is_some()),
+            ("jitter_ms", attributes.jitter_ms.is_some()),
+            ("bandwidth_bps", attributes.bandwidth_bps.is_some()),
+            ("mtu", attributes.mtu.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        if !ignored.is_empty() {
+            warnings.report(format!(
+                "Link '{}' sets {}, which pocketscion 0.4 does not simulate",
+                attributes.link,
+                ignored.join(", ")
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract the ISD-ASes of both ends of a link string (`<AS1>#<if> <type> <AS2>#<if>`)
+fn link_ases(link_str: &str) -> anyhow::Result<[IsdAsn; 2]> {
+    let [(from, _), (to, _)] = link_ends(link_str)?;
+    Ok([from, to])
+}
+
+/// Extract the ISD-ASes and interface IDs of both ends of a link string
+/// (`<AS1>#<if> <type> <AS2>#<if>`)
+pub(crate) fn link_ends(link_str: &str) -> anyhow::Result<[(IsdAsn, u16); 2]> {
+    let parts: Vec<&str> = link_str.split_whitespace().collect();
+    let [from, _, to] = parts.as_slice() else {
+        anyhow::bail!("Invalid link '{link_str}', expected '<AS1>#<if> <type> <AS2>#<if>'");
+    };
+
+    let parse_end = |end: &str| -> anyhow::Result<(IsdAsn, u16)> {
+        let (isd_as, interface) = end
+            .split_once('#')
+            .with_context(|| format!("Invalid link end '{end}' in link '{link_str}'"))?;
+        let interface = interface
+            .parse()
+            .with_context(|| format!("Invalid interface '{interface}' in link '{link_str}'"))?;
+        Ok((
+            parse_isd_as(isd_as, &format!("link '{link_str}'"))?,
+            interface,
+        ))
+    };
+
+    Ok([parse_end(from)?, parse_end(to)?])
+}
+
+/// Parse an ISD-AS, naming the config entry it belongs to (e.g. "routers[2]") in the error
+pub fn parse_isd_as(isd_as: &str, context: &str) -> anyhow::Result<IsdAsn> {
+    isd_as
+        .parse()
+        .with_context(|| format!("Invalid ISD-AS '{isd_as}' in {context}"))
+}
+
+/// The smallest MTU of a link, the minimum datagram size every IPv4 host must accept
+const MIN_LINK_MTU: u16 = 576;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_as_is_rejected_with_both_positions() {
+        let error = ConfigBuilder::new()
+            .with_as("1-1", true)
+            .with_as("1-11", false)
+            .with_as("1-11", true)
+            .with_link("1-1#1 parent_of 1-11#1")
+            .build()
+            .unwrap_err();
+
+        let message = format!("{error:#}");
+        assert!(message.contains("duplicate AS 1-11"), "{message}");
+        assert!(message.contains("topology.ases[1]"), "{message}");
+        assert!(message.contains("topology.ases[2]"), "{message}");
+    }
+
+    #[test]
+    fn snap_ids_keep_the_index_of_the_config() {
+        let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+            "topology": {
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-2", "is_core": true },
+                    { "isd_as": "1-3", "is_core": true }
+                ],
+                "links": ["1-1#1 core 1-2#1", "1-2#2 core 1-3#1"]
+            },
+            "snaps": [
+                {
+                    "listening_addr": "127.0.0.1:0",
+                    "data_plane": { "isd_as": "1-1", "listening_addr": "127.0.0.1:0" }
+                },
+                {
+                    "enabled": false,
+                    "listening_addr": "127.0.0.1:0",
+                    "data_plane": { "isd_as": "1-2", "listening_addr": "127.0.0.1:0" }
+                },
+                {
+                    "listening_addr": "127.0.0.1:0",
+                    "data_plane": { "isd_as": "1-3", "listening_addr": "127.0.0.1:0" }
+                }
+            ]
+        }))
+        .unwrap();
+        let (state, _, snap_ids) = build_runtime_state(&config, ConfigWarnings::Log).unwrap();
+
+        let indices: Vec<usize> = snap_ids.iter().map(|&(index, _)| index).collect();
+        assert_eq!(indices, [0, 2]);
+        let ids: Vec<SnapId> = snap_ids.iter().map(|&(_, id)| id).collect();
+        assert_eq!(ids, state.snaps().keys().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn an_isd_served_by_two_endhost_apis_is_reported() {
+        let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+            "topology": {
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-2", "is_core": false }
+                ],
+                "links": ["1-1#1 parent_of 1-2#1"]
+            },
+            "endhost_apis": [
+                { "isds": ["1-1"], "listening_addr": "127.0.0.1:10121" },
+                { "isds": ["1-2"], "listening_addr": "127.0.0.1:10122" }
+            ]
+        }))
+        .unwrap();
+        let error = validate_config(&config, ConfigWarnings::Deny, true).unwrap_err();
+
+        let message = format!("{error:#}");
+        assert!(
+            message.contains(
+                "ISD 1 is served by more than one endhost API: 127.0.0.1:10121, 127.0.0.1:10122"
+            ),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn all_errors_are_collected_without_fail_fast() {
+        let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+            "topology": {
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-2", "is_core": false }
+                ],
+                "links": ["1-1#1 parent_of 1-2#1", "garbage link", "1-1#2 parent_of 1-9#1"]
+            },
+            "routers": [
+                { "isd_as": "1-1", "interfaces": [0, 0, 2, 2], "listening_addr": "127.0.0.1:0" }
+            ]
+        }))
+        .unwrap();
+        let error = validate_config(&config, ConfigWarnings::Log, false).unwrap_err();
+
+        let message = format!("{error:#}");
+        assert!(message.contains("Found 5 errors"), "{message}");
+        assert!(
+            message.contains("topology.links[1] 'garbage link'"),
+            "{message}"
+        );
+        assert!(message.contains("references AS 1-9"), "{message}");
+        assert!(message.contains("routers[0].interfaces[1]"), "{message}");
+        assert!(
+            message.contains("Interface ID 2 is listed more than once"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn auth_token_requires_a_loopback_management_api() {
+        let config = |management_listen_addr: &str| {
+            let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+                "topology": { "ases": [{ "isd_as": "1-1", "is_core": true }], "links": [] },
+                "management_listen_addr": management_listen_addr,
+                "control_auth_token": "secret",
+            }))
+            .unwrap();
+            validate_config(&config, ConfigWarnings::Log, true)
+        };
+
+        let error = format!("{:#}", config("0.0.0.0:0").unwrap_err());
+        assert!(error.contains("must be a loopback address"), "{error}");
+        config("127.0.0.1:0").unwrap();
+    }
+
+    #[test]
+    fn duplicate_and_reversed_links_are_rejected() {
+        let topology: TopologyConfig = serde_json::from_value(serde_json::json!({
+            "ases": [
+                { "isd_as": "1-1", "is_core": true },
+                { "isd_as": "1-2", "is_core": true },
+                { "isd_as": "1-3", "is_core": false }
+            ],
+            "links": [
+                "1-1#1 core 1-2#1",
+                "1-1#2 parent_of 1-3#1",
+                "1-1#1 core 1-2#1",
+                "1-3#1 child_of 1-1#2"
+            ]
+        }))
+        .unwrap();
+
+        let error = format!("{:#}", check_duplicate_links(&topology).unwrap_err());
+        assert!(
+            error.contains(
+                "topology.links[2] '1-1#1 core 1-2#1' is the same link as topology.links[0]"
+            ),
+            "{error}"
+        );
+        assert!(
+            error.contains(
+                "topology.links[3] '1-3#1 child_of 1-1#2' is the same link as topology.links[1]"
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn jitter_requires_latency() {
+        let link = |attributes: serde_json::Value| {
+            let mut link = serde_json::json!({ "link": "1-1#1 parent_of 1-2#1" });
+            link.as_object_mut()
+                .unwrap()
+                .extend(attributes.as_object().unwrap().clone());
+            let config: PocketScionConfig = serde_json::from_value(serde_json::json!({
+                "topology": {
+                    "ases": [
+                        { "isd_as": "1-1", "is_core": true },
+                        { "isd_as": "1-2", "is_core": false }
+                    ],
+                    "links": [link]
+                }
+            }))
+            .unwrap();
+            validate_config(&config, ConfigWarnings::Log, true)
+                .map_err(|error| format!("{error:#}"))
+        };
+
+        let error = link(serde_json::json!({ "jitter_ms": 5 })).unwrap_err();
+        assert!(
+            error.contains("sets jitter_ms without latency_ms"),
+            "{error}"
+        );
+        let error = link(serde_json::json!({ "latency_ms": 5, "jitter_ms": 10 })).unwrap_err();
+        assert!(
+            error.contains("jitter_ms larger than its latency_ms"),
+            "{error}"
+        );
+        link(serde_json::json!({ "latency_ms": 10, "jitter_ms": 10 })).unwrap();
+    }
+
+    #[test]
+    fn links_inherit_the_smaller_default_of_their_ases() {
+        let mut config = ConfigBuilder::new()
+            .with_as("1-1", true)
+            .with_as("1-2", true)
+            .with_as("1-3", false)
+            .with_link("1-1#1 core 1-2#1")
+            .with_link("1-1#2 parent_of 1-3#1")
+            .build()
+            .unwrap()
+            .topology;
+        config.ases[0].default_mtu = Some(1400);
+        config.ases[0].default_bandwidth_bps = Some(1_000_000);
+        config.ases[1].default_mtu = Some(1300);
+        config.ases[2].default_bandwidth_bps = Some(5_000_000);
+        config.links[1] = LinkConfig::Detailed(DetailedLinkConfig {
+            link: "1-1#2 parent_of 1-3#1".to_string(),
+            relationship: None,
+            latency_ms: None,
+            jitter_ms: None,
+            bandwidth_bps: None,
+            mtu: Some(9000),
+        });
+
+        let attributes = link_attributes(&config);
+        let core = attributes[0].as_ref().unwrap();
+        assert_eq!(core.mtu, Some(1300));
+        assert_eq!(core.bandwidth_bps, Some(1_000_000));
+        let parent = attributes[1].as_ref().unwrap();
+        assert_eq!(parent.mtu, Some(9000));
+        assert_eq!(parent.bandwidth_bps, Some(1_000_000));
+    }
+
+    #[tokio::test]
+    async fn two_ases_start_and_shut_down_in_process() {
+        let localhost = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        let config = ConfigBuilder::new()
+            .with_as("1-1", true)
+            .with_as("1-2", false)
+            .with_link("1-1#1 parent_of 1-2#1")
+            .with_snap(localhost(0), "1-2", localhost(0))
+            .build()
+            .unwrap();
+
+        let mut runtime = start_runtime(&config).await.unwrap();
+        let snaps = runtime.api_client().get_snaps().await.unwrap().snaps;
+        assert_eq!(snaps.len(), 1);
+
+        shutdown_runtime(&mut runtime, Duration::from_secs(5)).await;
+    }
+}
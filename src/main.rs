@@ -1,238 +1,1429 @@
 use std::collections::BTreeMap;
 use std::net::SocketAddr;
-use std::num::NonZeroU16;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
-use clap::Parser;
-use ipnet::IpNet;
-use pocketscion::io_config;
-use pocketscion::network::scion::topology::{ScionAs, ScionTopology};
-use pocketscion::runtime::{PocketScionRuntime, PocketScionRuntimeBuilder};
-use pocketscion::state::SharedPocketScionState;
-use scion_proto::address::IsdAsn;
-use serde::{Deserialize, Serialize};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pocketscion_configurator::{
+    AsConfig, ConfigFormat, ConfigWarnings, DataPlaneConfig, EndhostApiConfig, LinkConfig,
+    LoadOptions, LoggingConfig, PocketScionConfig, RouterConfig, SnapConfig, TopologyConfig,
+    TopologyLimits, UnknownFields, build_runtime_state, build_topology_from_config, control, diff,
+    dot, health, import, inspect, load_configs, load_vars, log_snap_ids, manifest, metrics,
+    parse_isd_as, ports, reachability, scale, schema, shutdown_runtime, start_runtime_with_retries,
+    tokens, unwatchable_source, validate_config,
+};
 use snap_tokens::v0::dummy_snap_token;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Registry, reload};
 
-/// Pocket SCION Configurator - Configure and run pocketscion simulator with networks from JSON files
+mod watch;
+
+/// Pocket SCION Configurator - Configure and run pocketscion simulator with networks from JSON or YAML files
 #[derive(Parser, Debug)]
 #[command(name = "pocketscion-configurator")]
-#[command(about = "Configure and run the pocketscion simulator with networks from JSON files", long_about = None)]
+#[command(about = "Configure and run the pocketscion simulator with networks from JSON or YAML files", long_about = None)]
 struct Cli {
-    /// Path to the configuration file
-    #[arg(short, long, default_value = "config.json")]
-    config: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the configuration file (JSON or YAML), or `-` to read it from stdin. Can be given
+    /// multiple times to merge several configs in order
+    #[arg(short, long, default_value = "config.json", global = true)]
+    config: Vec<String>,
+
+    /// Directory whose JSON and YAML files are merged in lexical order, instead of --config
+    #[arg(long, global = true, conflicts_with = "config")]
+    config_dir: Option<String>,
+
+    /// Parse the configs as json or yaml, instead of by their file extension. Included fragments
+    /// are still parsed by their extension
+    #[arg(long, global = true, value_name = "FORMAT")]
+    config_format: Option<ConfigFormat>,
+
+    /// Ignore fields of the config that this version does not know, with a warning, instead of
+    /// rejecting the config
+    #[arg(long, global = true)]
+    allow_unknown_fields: bool,
+
+    /// JSON or YAML file mapping names to the values substituted for `${NAME}` in the configs
+    #[arg(long, global = true, value_name = "PATH")]
+    vars: Option<String>,
+
+    /// Set the config variable NAME, overriding --vars. Can be given multiple times
+    #[arg(long = "set", global = true, value_name = "NAME=VALUE", value_parser = parse_var)]
+    set: Vec<(String, String)>,
+
+    /// Tracing level (trace, debug, info, warn, error), used if neither --log-filter nor
+    /// RUST_LOG is given. Defaults to the `logging` section of the config, or to info
+    #[clap(long = "log", global = true)]
+    log_level: Option<tracing::Level>,
+
+    /// Only log warnings and errors, like `--log warn`
+    #[arg(short, long, global = true, conflicts_with = "log_level")]
+    quiet: bool,
 
-    /// Tracing level (trace, debug, info, warn, error)
-    #[clap(long = "log", default_value = "info")]
-    log_level: tracing::Level,
+    /// Tracing filter directives like `pocketscion=warn,pocketscion_configurator=debug`, takes
+    /// precedence over RUST_LOG
+    #[arg(long, global = true)]
+    log_filter: Option<String>,
+
+    /// Format of the log output
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+
+    /// Also write logs to this file, rotated daily by appending the date to the file name
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Arguments of the `run` command, which is used if no command is given
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+/// Format of the log output
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, for log pipelines
+    Json,
+}
+
+/// Format of the output of the read-only subcommands
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Tables and lines for humans
+    Text,
+    /// A JSON document, for scripts
+    Json,
+}
 
-    /// Path to write the SNAP token file
+/// Where the SNAP tokens are put, see `--token-sink`
+#[derive(Clone, Debug)]
+enum TokenSink {
+    /// Token files, at --token-file or in --token-dir
+    File,
+    /// One token per line on stdout
+    Stdout,
+    /// `export NAME=<token>` lines on stdout
+    Env(String),
+}
+
+fn parse_token_sink(sink: &str) -> Result<TokenSink, String> {
+    match sink {
+        "file" => Ok(TokenSink::File),
+        "stdout" => Ok(TokenSink::Stdout),
+        _ => match sink.strip_prefix("env:") {
+            Some(name) if is_env_var_name(name) => Ok(TokenSink::Env(name.to_string())),
+            Some(name) => Err(format!(
+                "'{name}' is not a valid environment variable name, expected letters, digits \
+                 and underscores, not starting with a digit"
+            )),
+            None => Err("expected file, stdout or env:NAME".to_string()),
+        },
+    }
+}
+
+fn parse_var(var: &str) -> Result<(String, String), String> {
+    let (name, value) = var
+        .split_once('=')
+        .ok_or_else(|| "expected NAME=VALUE".to_string())?;
+    if !is_env_var_name(name) {
+        return Err(format!(
+            "'{name}' is not a valid variable name, expected letters, digits and underscores, \
+             not starting with a digit"
+        ));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start the simulator with the network from the config file (default)
+    Run(RunArgs),
+    /// Write an example config to the path given by --config, or to stdout with `-c -`
+    Generate {
+        /// Overwrite the config file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the JSON Schema of the config to stdout, for completion and validation in editors
+    Schema,
+    /// Create a config from SCION topology files and write it to the path given by --config, or
+    /// to stdout with `-c -`
+    Import {
+        /// Path to a SCION `topology.json` describing one AS, can be given multiple times
+        #[arg(long = "import-scion-topology", required = true)]
+        scion_topologies: Vec<String>,
+        /// Overwrite the config file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print a table of the ASes of the config, with their links, SNAPs and routers
+    Inspect {
+        /// Print text for humans or JSON for scripts
+        #[arg(long, value_enum, default_value = "text", value_name = "FORMAT")]
+        output_format: OutputFormat,
+    },
+    /// Check whether a SCION path from one AS to another can exist over the links of the
+    /// topology, and print one such path
+    Reachable {
+        /// The ISD-AS the path starts at
+        src: String,
+        /// The ISD-AS the path ends at
+        dst: String,
+        /// Print text for humans or JSON for scripts
+        #[arg(long, value_enum, default_value = "text", value_name = "FORMAT")]
+        output_format: OutputFormat,
+    },
+    /// Replicate the non-core ASes of the config, for load tests with large topologies, and
+    /// write the scaled config to --output
+    Scale {
+        /// The number of copies of every non-core AS, including the original
+        factor: u32,
+        /// Path to write the scaled config to, or `-` for stdout
+        #[arg(long, default_value = "-")]
+        output: String,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Compare two configs and print the ASes, links, SNAPs, endhost APIs and routers that were
+    /// added, removed or changed
+    Diff {
+        /// The old config
+        old: String,
+        /// The new config
+        new: String,
+        /// Print text for humans or JSON for scripts
+        #[arg(long, value_enum, default_value = "text", value_name = "FORMAT")]
+        output_format: OutputFormat,
+    },
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Path to write the SNAP token file to, if there is a single SNAP or with --dummy-token, or
+    /// `-` to print the token to stdout
     #[arg(long = "token-file", default_value = "./snap.token")]
     token_file: String,
+
+    /// Directory to write the token of each SNAP to, if there is more than one SNAP
+    #[arg(long = "token-dir", default_value = "./snap-tokens")]
+    token_dir: String,
+
+    /// Write a single dummy SNAP token, signed with the insecure built-in key, to --token-file
+    /// instead of a token per SNAP
+    #[arg(long = "dummy-token")]
+    dummy_token: bool,
+
+    /// Where to put the SNAP tokens: `file` writes them to --token-file or --token-dir, `stdout`
+    /// prints them and `env:NAME` prints `export NAME=<token>` lines to eval in a shell
+    #[arg(
+        long = "token-sink",
+        value_name = "SINK",
+        default_value = "file",
+        value_parser = parse_token_sink,
+        conflicts_with = "no_token_file"
+    )]
+    token_sink: TokenSink,
+
+    /// Do not write any SNAP token files, the tokens are only logged at debug level
+    #[arg(long = "no-token-file", conflicts_with_all = ["token_file", "token_dir"])]
+    no_token_file: bool,
+
+    /// Only validate the config and print a summary, without starting the runtime
+    #[arg(long)]
+    validate: bool,
+
+    /// Seconds to wait for the runtime to stop after a shutdown signal
+    #[arg(long = "shutdown-timeout", default_value_t = 5)]
+    shutdown_timeout: u64,
+
+    /// Print the parsed config, including defaulted fields, as JSON and exit
+    #[arg(long = "dump-config")]
+    dump_config: bool,
+
+    /// Write the topology as a Graphviz DOT graph to this path, or to stdout with `-`
+    #[arg(long = "export-dot")]
+    export_dot: Option<String>,
+
+    /// Write the listen addresses as JSON to this path once the simulator is running, including
+    /// the ports picked for addresses with port 0
+    #[arg(long = "print-ports-file")]
+    print_ports_file: Option<String>,
+
+    /// Write a JSON manifest of the running simulator to this path, with the IDs pocketscion
+    /// assigned to its SNAPs, endhost APIs and routers
+    #[arg(long = "manifest-file")]
+    manifest_file: Option<String>,
+
+    /// Reload the config, like on SIGHUP, whenever the config file or one of its includes changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Check that every listen address can be bound before starting the simulator, and report
+    /// all that can not
+    #[arg(long = "check-ports")]
+    check_ports: bool,
+
+    /// Warn about listen addresses whose IP is not an address of this host, also with --validate
+    #[arg(long = "check-interfaces")]
+    check_interfaces: bool,
+
+    /// Skip the SNAPs, endhost APIs and routers whose listen addresses can not be bound, instead
+    /// of failing the startup
+    #[arg(long = "continue-on-bind-error", conflicts_with = "validate")]
+    continue_on_bind_error: bool,
+
+    /// Try to start the simulator this many more times if a listen address is still in use, for
+    /// example in TIME_WAIT from a previous run
+    #[arg(long = "start-retries", default_value_t = 0)]
+    start_retries: u32,
+
+    /// Treat warnings about the config as errors, and reject unknown fields even with
+    /// --allow-unknown-fields
+    #[arg(long)]
+    strict: bool,
+
+    /// Stop validating the config at its first error. With --fail-fast=false, all errors of the
+    /// config are collected and reported together.
+    #[arg(
+        long = "fail-fast",
+        default_value_t = true,
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    fail_fast: bool,
+
+    /// Start only the topology and the management API, ignoring the SNAPs, endhost APIs and
+    /// routers of the config
+    #[arg(long = "topology-only")]
+    topology_only: bool,
+
+    /// Reject configs whose topology has more ASes than this, before anything is started
+    #[arg(long = "max-ases", value_name = "N")]
+    max_ases: Option<usize>,
+
+    /// Reject configs whose topology has more links than this, before anything is started
+    #[arg(long = "max-links", value_name = "N")]
+    max_links: Option<usize>,
+
+    /// Give up if the simulator has not started after this many seconds, including all
+    /// retries of --start-retries
+    #[arg(long = "start-timeout", value_name = "SECONDS")]
+    start_timeout: Option<u64>,
+
+    /// Log how long each phase of the startup took, from reading the config to starting the
+    /// servers
+    #[arg(long)]
+    profile: bool,
+
+    /// Milliseconds to wait before each retry of --start-retries
+    #[arg(long = "start-retry-delay", value_name = "MS", default_value_t = 1000)]
+    start_retry_delay: u64,
+}
+
+impl RunArgs {
+    fn config_warnings(&self) -> ConfigWarnings {
+        if self.strict {
+            ConfigWarnings::Deny
+        } else {
+            ConfigWarnings::Log
+        }
+    }
+
+    fn topology_limits(&self) -> TopologyLimits {
+        TopologyLimits {
+            max_ases: self.max_ases,
+            max_links: self.max_links,
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    match run_cli(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            let failure = e.downcast_ref::<Failure>();
+            ExitCode::from(failure.map_or(1, |failure| failure.exit_code()))
+        }
+    }
+}
+
+/// Kind of failure that is reported with its own exit code, attached to errors as context
+#[derive(Debug, Clone, Copy)]
+enum Failure {
+    /// A config file or directory does not exist
+    ConfigNotFound,
+    /// A config file could not be parsed or merged
+    InvalidSyntax,
+    /// The config does not describe a valid network
+    InvalidConfig,
+    /// The simulator or one of its servers could not be started
+    Startup,
+    /// `reachable` found no SCION path in a valid config
+    Unreachable,
+}
 
-    tracing_subscriber::fmt()
-        .with_max_level(cli.log_level)
+impl Failure {
+    /// The exit code of the process. 1 is used for other errors and 2 by clap for invalid
+    /// arguments.
+    fn exit_code(self) -> u8 {
+        match self {
+            Failure::ConfigNotFound => 3,
+            Failure::InvalidSyntax => 4,
+            Failure::InvalidConfig => 5,
+            Failure::Startup => 6,
+            Failure::Unreachable => 7,
+        }
+    }
+
+    /// The failure of an error returned while loading the config
+    fn of_load_error(error: &anyhow::Error) -> Self {
+        let not_found = error.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+                || cause
+                    .downcast_ref::<reqwest::Error>()
+                    .is_some_and(|e| e.status() == Some(reqwest::StatusCode::NOT_FOUND))
+        });
+        if not_found {
+            Failure::ConfigNotFound
+        } else {
+            Failure::InvalidSyntax
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Failure::ConfigNotFound => "Config not found",
+            Failure::InvalidSyntax => "Failed to parse config",
+            Failure::InvalidConfig => "Invalid config",
+            Failure::Startup => "Failed to start the simulator",
+            Failure::Unreachable => "AS unreachable",
+        })
+    }
+}
+
+/// Run the command given on the command line
+async fn run_cli(cli: Cli) -> anyhow::Result<()> {
+    // Logs written to the file are lost once the guard is dropped, so keep it until the end
+    let logging = init_logging(&cli)?;
+
+    let config_paths = match &cli.config_dir {
+        Some(dir) => vec![dir.clone()],
+        None => cli.config.clone(),
+    };
+    let config_dir = cli.config_dir.as_deref();
+    let mut vars = match &cli.vars {
+        Some(path) => load_vars(path).map_err(|e| {
+            let failure = Failure::of_load_error(&e);
+            e.context(failure)
+        })?,
+        None => BTreeMap::new(),
+    };
+    vars.extend(cli.set.iter().cloned());
+    let load_options = LoadOptions {
+        format: cli.config_format,
+        unknown_fields: if cli.allow_unknown_fields {
+            UnknownFields::Warn
+        } else {
+            UnknownFields::Deny
+        },
+        vars,
+    };
+
+    match cli.command.unwrap_or(Command::Run(cli.run)) {
+        Command::Run(args) => match run(&config_paths, &load_options, &args, &logging).await? {
+            RunExit::Stopped => Ok(()),
+            RunExit::Restart => {
+                // The process image is replaced without running destructors, so flush the logs
+                drop(logging);
+                re_exec()
+            }
+        },
+        Command::Generate { force } => {
+            generate(single_config_path(&cli.config, config_dir)?, force)
+        }
+        Command::Schema => print_schema(),
+        Command::Import {
+            scion_topologies,
+            force,
+        } => import(
+            single_config_path(&cli.config, config_dir)?,
+            &scion_topologies,
+            force,
+        ),
+        Command::Inspect { output_format } => inspect(&config_paths, &load_options, output_format),
+        Command::Reachable {
+            src,
+            dst,
+            output_format,
+        } => reachable(&config_paths, &load_options, &src, &dst, output_format),
+        Command::Scale {
+            factor,
+            output,
+            force,
+        } => scale(&config_paths, &load_options, factor, &output, force),
+        Command::Diff {
+            old,
+            new,
+            output_format,
+        } => diff(&old, &new, &load_options, output_format),
+    }
+}
+
+/// The path to write a config to, for commands that only support a single --config
+fn single_config_path<'a>(
+    paths: &'a [String],
+    config_dir: Option<&str>,
+) -> anyhow::Result<&'a str> {
+    if config_dir.is_some() {
+        anyhow::bail!("A config can not be written to a --config-dir, use --config instead");
+    }
+    match paths {
+        [path] => Ok(path),
+        _ => anyhow::bail!("Only a single --config can be written, got {}", paths.len()),
+    }
+}
+
+/// The parts of the logging setup that are needed while the configurator runs
+struct Logging {
+    /// Guard of the `--log-file` writer, which flushes the remaining logs when dropped
+    _file_guard: Option<WorkerGuard>,
+    /// Handle to replace the filter with the `logging` section of the config, if logging is not
+    /// configured on the command line or with RUST_LOG
+    config_filter: Option<reload::Handle<EnvFilter, Registry>>,
+}
+
+impl Logging {
+    /// Filter the logs with the levels of `config`, unless they are overridden
+    fn apply_config(&self, config: &LoggingConfig) -> anyhow::Result<()> {
+        let Some(handle) = &self.config_filter else {
+            return Ok(());
+        };
+        handle
+            .reload(config.env_filter()?)
+            .context("Failed to apply the logging config")
+    }
+}
+
+/// Set up logging to stderr and to the `--log-file`, if given
+fn init_logging(cli: &Cli) -> anyhow::Result<Logging> {
+    let level = if cli.quiet {
+        tracing::Level::WARN
+    } else {
+        cli.log_level.unwrap_or(tracing::Level::INFO)
+    };
+    let filter = EnvFilter::builder().with_default_directive(LevelFilter::from_level(level).into());
+    let filter = match &cli.log_filter {
+        Some(directives) => filter
+            .parse(directives)
+            .context(format!("Invalid --log-filter '{directives}'"))?,
+        None => filter.from_env().context("Invalid RUST_LOG")?,
+    };
+    let configured = cli.log_filter.is_some()
+        || cli.log_level.is_some()
+        || cli.quiet
+        || std::env::var_os(EnvFilter::DEFAULT_ENV).is_some();
+    let (filter, handle) = reload::Layer::new(filter);
+
+    // Log to stderr, so configs written to stdout can be piped
+    let stderr_layer = log_layer(cli.log_format, std::io::stderr, true);
+
+    let (file_layer, guard) = match &cli.log_file {
+        Some(path) => {
+            let file_name = path
+                .file_name()
+                .context(format!("Invalid log file: {}", path.display()))?;
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let appender = tracing_appender::rolling::daily(dir, file_name);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(log_layer(cli.log_format, writer, false)), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr_layer)
+        .with(file_layer)
         .init();
 
-    tracing::info!("Reading config from: {}", cli.config);
-    let config_content = std::fs::read_to_string(&cli.config)
-        .context(format!("Failed to read config file: {}", cli.config))?;
+    Ok(Logging {
+        _file_guard: guard,
+        config_filter: (!configured).then_some(handle),
+    })
+}
+
+/// A formatting layer writing to `writer` in the given format
+fn log_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// Why the simulator stopped running
+enum RunExit {
+    /// Stopped by a signal or done with a command that does not start the simulator
+    Stopped,
+    /// Stopped to be re-executed with a reloaded config
+    Restart,
+}
+
+/// How long each phase of the startup took, logged with `--profile`
+struct StartupProfile {
+    enabled: bool,
+    started: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    fn new(enabled: bool) -> Self {
+        let now = Instant::now();
+        StartupProfile {
+            enabled,
+            started: now,
+            last: now,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Record the time since the previous phase as the duration of `phase`
+    fn phase_done(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    fn log(&self) {
+        if !self.enabled {
+            return;
+        }
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(phase, duration)| format!("{phase} {duration:.1?}"))
+            .collect();
+        tracing::info!(
+            "Startup took {:.1?}: {}",
+            self.last - self.started,
+            phases.join(", ")
+        );
+    }
+}
+
+/// Start the pocketscion runtime with the network from the config file and run until shutdown
+async fn run(
+    config_paths: &[String],
+    load_options: &LoadOptions,
+    args: &RunArgs,
+    logging: &Logging,
+) -> anyhow::Result<RunExit> {
+    let mut profile = StartupProfile::new(args.profile);
+    let strict_options;
+    let load_options = if args.strict {
+        strict_options = LoadOptions {
+            unknown_fields: UnknownFields::Deny,
+            ..load_options.clone()
+        };
+        &strict_options
+    } else {
+        load_options
+    };
+    let mut pocket_scion = load_configs(config_paths, load_options).map_err(|e| {
+        let failure = Failure::of_load_error(&e);
+        e.context(failure)
+    })?;
+    if let Some(logging_config) = &pocket_scion.logging {
+        logging
+            .apply_config(logging_config)
+            .context(Failure::InvalidConfig)?;
+    }
+    let limits = args.topology_limits();
+    limits
+        .check(&pocket_scion.topology)
+        .context(Failure::InvalidConfig)?;
+    profile.phase_done("load config");
+    // The config as read, for telling on reload which sections changed
+    let loaded = pocket_scion.clone();
+
+    if args.dump_config {
+        let config =
+            serde_json::to_string_pretty(&pocket_scion).context("Failed to serialize config")?;
+        println!("{config}");
+        return Ok(RunExit::Stopped);
+    }
+    if args.topology_only {
+        ignore_non_topology_sections(&mut pocket_scion);
+    }
+
+    // A config that is only validated must not open any sockets
+    if !args.validate {
+        ports::resolve_ephemeral_ports(&mut pocket_scion).context(Failure::Startup)?;
+        profile.phase_done("resolve ephemeral ports");
+    }
+    if args.continue_on_bind_error {
+        ports::disable_unbindable(&mut pocket_scion);
+    }
+
+    if !args.fail_fast {
+        validate_config(&pocket_scion, args.config_warnings(), false)
+            .context(Failure::InvalidConfig)?;
+    }
+    let (mut system_state, io_config, snap_ids) =
+        build_runtime_state(&pocket_scion, args.config_warnings())
+            .context(Failure::InvalidConfig)?;
+    profile.phase_done("build topology and state");
 
-    let pocket_scion: PocketScionConfig =
-        serde_json::from_str(&config_content).context("Failed to parse config file")?;
+    if args.check_interfaces {
+        ports::check_interfaces(&pocket_scion, args.config_warnings())
+            .context(Failure::InvalidConfig)?;
+    }
 
-    // Build topology from config
-    let topology = build_topology_from_config(&pocket_scion.topology)?;
+    if let Some(path) = &args.export_dot {
+        export_dot(&pocket_scion.topology, path)?;
+    }
 
-    let _pocket_scion_runtime = {
-        tracing::info!("Starting Pocket SCION runtime...");
+    if args.validate {
+        profile.log();
+        print_config_summary(&pocket_scion);
+        return Ok(RunExit::Stopped);
+    }
 
-        let mut system_state = SharedPocketScionState::new(SystemTime::now());
-        let io_config = io_config::SharedPocketScionIoConfig::new();
+    let watcher = if args.watch {
+        if let Some(source) = config_paths
+            .iter()
+            .find_map(|path| unwatchable_source(path))
+        {
+            anyhow::bail!("--watch can not be used with a config {source}");
+        }
+        tracing::info!(
+            "Watching {} config file(s) for changes",
+            pocket_scion.sources.len()
+        );
+        Some(watch::ConfigWatcher::new(pocket_scion.sources.clone()))
+    } else {
+        None
+    };
+    let mut signals = ControlSignals::new(watcher)?;
 
-        // Set the topology
-        system_state.set_topology(topology.clone());
+    // Without the dummy token, SNAPs verify tokens with a key generated for this run
+    let token_issuer = if args.dummy_token {
+        None
+    } else {
+        let issuer = tokens::SnapTokenIssuer::generate()?;
+        system_state.set_snap_token_public_pem(issuer.public_pem());
+        Some(issuer)
+    };
 
-        // Create SCION Network Access Points (SNAPs) if present
-        if let Some(snaps) = &pocket_scion.snaps {
-            for snap in snaps {
-                let isd_as: IsdAsn = snap.data_plane.isd_as.parse()?;
+    if args.check_ports {
+        ports::check_bindable(&pocket_scion).context(Failure::Startup)?;
+        profile.phase_done("check ports");
+    }
 
-                // Add a new SNAP to the system state
-                let snap_id = system_state.add_snap(isd_as)?;
+    let readiness = health::Readiness::default();
+    let health_server = match pocket_scion.health_listen_addr {
+        Some(addr) => {
+            let (addr, server) = health::serve(addr, readiness.clone())
+                .await
+                .context(Failure::Startup)?;
+            pocket_scion.health_listen_addr = Some(addr);
+            Some(server)
+        }
+        None => None,
+    };
+
+    // The runtime does not share its state, the control API lists paths from a copy
+    let control = pocket_scion
+        .control_listen_addr
+        .map(|addr| (addr, system_state.system_state().clone()));
+    let start = start_runtime_with_retries(
+        &pocket_scion,
+        system_state,
+        io_config,
+        args.start_retries,
+        Duration::from_millis(args.start_retry_delay),
+    );
+    // Dropping the unfinished start aborts the tasks that were already spawned
+    let mut pocket_scion_runtime = match args.start_timeout {
+        Some(secs) => {
+            tracing::info!("Waiting at most {}s for the simulator to start", secs);
+            tokio::time::timeout(Duration::from_secs(secs), start)
+                .await
+                .map_err(|_| anyhow::anyhow!("Pocket SCION runtime did not start within {secs}s"))
+                .context(Failure::Startup)?
+        }
+        None => start.await,
+    }
+    .context(Failure::Startup)?;
+    log_snap_ids(&pocket_scion, &snap_ids);
+    readiness.set_ready(true);
+    profile.phase_done("start runtime");
 
-                // Then add an IO config to declare how this control plane can be reached
-                io_config.set_snap_control_addr(snap_id, snap.listening_addr);
+    let metrics_server = match pocket_scion.metrics_listen_addr {
+        Some(addr) => {
+            let metrics = metrics::Metrics::new()?;
+            metrics.set_config(&pocket_scion);
+            let (addr, server) = metrics::serve(addr, metrics)
+                .await
+                .context(Failure::Startup)?;
+            pocket_scion.metrics_listen_addr = Some(addr);
+            Some(server)
+        }
+        None => None,
+    };
 
-                // Add an IO config
-                io_config.set_snap_data_plane_addr(snap_id, snap.data_plane.listening_addr);
+    let control_server = match control {
+        Some((addr, state)) => {
+            let control = control::Control::new(
+                pocket_scion_runtime.api_client(),
+                &pocket_scion.topology,
+                state,
+                pocket_scion.control_auth_token.clone(),
+            )?;
+            let (addr, server) = control::serve(addr, control)
+                .await
+                .context(Failure::Startup)?;
+            pocket_scion.control_listen_addr = Some(addr);
+            Some(server)
+        }
+        None => None,
+    };
+    profile.phase_done("start metrics and control servers");
+
+    tracing::info!("Example SCION testnet setup complete.");
+    profile.log();
+
+    if let Some(path) = &args.print_ports_file {
+        ports::ListenAddrs::collect(&pocket_scion_runtime.api_client(), &pocket_scion)
+            .await?
+            .write(path)?;
+    }
+    if let Some(path) = &args.manifest_file {
+        manifest::Manifest::collect(&pocket_scion_runtime.api_client(), &pocket_scion)
+            .await?
+            .write(path)?;
+    }
+
+    match &token_issuer {
+        Some(issuer) if args.no_token_file => log_snap_tokens(&pocket_scion, issuer)?,
+        Some(issuer) => write_snap_tokens(
+            &pocket_scion,
+            issuer,
+            &args.token_sink,
+            &args.token_file,
+            &args.token_dir,
+        )?,
+        None if args.no_token_file => tracing::debug!("Dummy SNAP token: {}", dummy_snap_token()),
+        None => {
+            let token = dummy_snap_token();
+            tracing::info!("Dummy SNAP token: {}", token);
+
+            if let TokenSink::Env(name) = &args.token_sink {
+                println!("export {name}={token}");
+                tracing::info!("Dummy SNAP token written to stdout as {}", name);
+            } else if matches!(args.token_sink, TokenSink::Stdout) || args.token_file == "-" {
+                println!("{token}");
+                tracing::info!("Dummy SNAP token written to stdout");
+            } else {
+                // store token on disk
+                write_token_file(Path::new(&args.token_file), &token)?;
+                tracing::info!("Dummy SNAP token written to '{}'", args.token_file);
             }
         }
+    }
 
-        // Configure endhost APIs if present
-        if let Some(endhost_apis) = &pocket_scion.endhost_apis {
-            for api_config in endhost_apis {
-                let isds: Vec<IsdAsn> = api_config
-                    .isds
-                    .iter()
-                    .map(|s| s.parse())
-                    .collect::<Result<Vec<_>, _>>()?;
-                let endhost_api_id = system_state.add_endhost_api(isds);
-                io_config.set_endhost_api_addr(endhost_api_id, api_config.listening_addr);
+    let shutdown_timeout = Duration::from_secs(args.shutdown_timeout);
+    loop {
+        match signals.recv().await? {
+            ControlSignal::Shutdown(signal) => {
+                tracing::info!("Received {}, stopping...", signal);
+                break;
+            }
+            ControlSignal::Reload(reason) => {
+                tracing::info!("{}, reloading config...", reason);
+                match reload(
+                    &loaded,
+                    config_paths,
+                    load_options,
+                    limits,
+                    args.config_warnings(),
+                    args.fail_fast,
+                ) {
+                    Ok(true) => return Ok(RunExit::Restart),
+                    Ok(false) => {}
+                    Err(e) => tracing::error!("Failed to reload config: {:#}", e),
+                }
             }
         }
+    }
+
+    readiness.set_ready(false);
+    shutdown_runtime(&mut pocket_scion_runtime, shutdown_timeout).await;
+    for server in [metrics_server, health_server, control_server]
+        .into_iter()
+        .flatten()
+    {
+        server.abort();
+    }
 
-        // Configure routers if present
-        if let Some(routers) = &pocket_scion.routers {
-            for router_config in routers {
-                let isd_as: IsdAsn = router_config.isd_as.parse()?;
-                let interfaces: Vec<NonZeroU16> = router_config
-                    .interfaces
-                    .iter()
-                    .map(|&i| NonZeroU16::new(i).context("Interface ID must be non-zero"))
-                    .collect::<Result<Vec<_>, _>>()?;
-
-                let router_id = system_state.add_router(
-                    isd_as,
-                    interfaces,
-                    router_config.snap_data_plane_excludes.clone(),
-                    router_config.snap_data_plane_interfaces.clone(),
-                );
-                io_config.set_router_socket_addr(router_id, router_config.listening_addr);
+    Ok(RunExit::Stopped)
+}
+
+/// Issue a token for every SNAP and print which file it was written to. A single SNAP gets
+/// `token_file`, with more SNAPs each token is written to `<isd_as>-<index>.token` in `token_dir`,
+/// where the index is the position of the SNAP in the config. The stdout and env sinks print the
+/// tokens in the order of the config instead, the env sink as `NAME_<index>` with more SNAPs.
+fn write_snap_tokens(
+    config: &PocketScionConfig,
+    issuer: &tokens::SnapTokenIssuer,
+    sink: &TokenSink,
+    token_file: &str,
+    token_dir: &str,
+) -> anyhow::Result<()> {
+    let snaps: Vec<_> = config.enabled_snaps().collect();
+    if snaps.is_empty() {
+        tracing::info!("No SNAPs configured, not writing any SNAP tokens");
+        return Ok(());
+    }
+    match sink {
+        TokenSink::File => {}
+        TokenSink::Stdout => {
+            for _ in &snaps {
+                println!("{}", issuer.issue()?);
             }
+            tracing::info!("SNAP tokens written to stdout");
+            return Ok(());
         }
+        TokenSink::Env(name) => {
+            for (index, _) in &snaps {
+                let var = match snaps.len() {
+                    1 => name.clone(),
+                    _ => format!("{name}_{index}"),
+                };
+                println!("export {var}={}", issuer.issue()?);
+            }
+            tracing::info!("SNAP tokens written to stdout as {}", name);
+            return Ok(());
+        }
+    }
+
+    let paths: Vec<PathBuf> = match snaps.as_slice() {
+        // Only the token is printed, so stdout can be piped into another process
+        [_] if token_file == "-" => {
+            println!("{}", issuer.issue()?);
+            tracing::info!("SNAP token written to stdout");
+            return Ok(());
+        }
+        [_] => vec![PathBuf::from(token_file)],
+        _ => {
+            std::fs::create_dir_all(token_dir)
+                .context(format!("Failed to create token directory {token_dir}"))?;
+            snaps
+                .iter()
+                .map(|(index, snap)| {
+                    // Colons are not allowed in file names on every platform
+                    let isd_as = snap.data_plane.isd_as.replace(':', "_");
+                    Path::new(token_dir).join(format!("{isd_as}-{index}.token"))
+                })
+                .collect()
+        }
+    };
+
+    println!("SNAP tokens:");
+    for ((_, snap), path) in snaps.iter().zip(&paths) {
+        write_token_file(path, &issuer.issue()?)?;
+        println!("  {} -> {}", snap.listening_addr, path.display());
+    }
+
+    Ok(())
+}
+
+/// Write a SNAP token to `path`, creating its parent directories if they do not exist yet
+fn write_token_file(path: &Path, token: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).context(format!(
+            "Failed to create directory {} for SNAP token file",
+            parent.display()
+        ))?;
+    }
+    std::fs::write(path, token).context(format!("Failed to write SNAP token to {}", path.display()))
+}
+
+/// Issue a token for every SNAP and only log it, for `--no-token-file`
+fn log_snap_tokens(
+    config: &PocketScionConfig,
+    issuer: &tokens::SnapTokenIssuer,
+) -> anyhow::Result<()> {
+    for (_, snap) in config.enabled_snaps() {
+        tracing::debug!(
+            "SNAP token for {}: {}",
+            snap.listening_addr,
+            issuer.issue()?
+        );
+    }
+    Ok(())
+}
+
+/// Write the topology as a Graphviz DOT graph to `path`, or to stdout if `path` is `-`
+fn export_dot(topology: &TopologyConfig, path: &str) -> anyhow::Result<()> {
+    let dot = dot::topology_to_dot(topology)?;
+
+    if path == "-" {
+        print!("{dot}");
+        return Ok(());
+    }
+
+    std::fs::write(path, dot).context(format!("Failed to write DOT graph to {path}"))?;
+    tracing::info!("Topology written as DOT graph to '{}'", path);
 
-        // Finally we create the PocketScionRuntime
-        let rt: PocketScionRuntime = PocketScionRuntimeBuilder::new()
-            .with_system_state(system_state.into_state())
-            .with_io_config(io_config.into_state())
-            .with_mgmt_listen_addr(pocket_scion.management_listen_addr)
-            .start()
-            .await
-            .context("error starting Pocket SCION runtime")?;
+    Ok(())
+}
 
-        tracing::info!("Pocket SCION runtime started");
+/// Re-read the config and check that it is valid. Returns whether the configurator should be
+/// re-executed in place to apply it, an invalid config is logged and the running simulator is kept.
+///
+/// pocketscion 0.4 neither allows changing the state of a running simulator nor releases all
+/// sockets of a stopped one, so replacing the process image is the only way to apply a new
+/// config. As that loses the SNAP sessions and tokens, a config that only changes the topology
+/// of `running` is rejected, since applying it would need `set_topology` on the running state.
+fn reload(
+    running: &PocketScionConfig,
+    config_paths: &[String],
+    load_options: &LoadOptions,
+    limits: TopologyLimits,
+    warnings: ConfigWarnings,
+    fail_fast: bool,
+) -> anyhow::Result<bool> {
+    if let Some(source) = config_paths
+        .iter()
+        .find_map(|path| unwatchable_source(path))
+    {
+        anyhow::bail!("A config {source} can not be reloaded");
+    }
 
-        rt
+    let config = match load_configs(config_paths, load_options) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Failed to read config, keeping the previous one: {:#}", e);
+            return Ok(false);
+        }
     };
+    if let Err(e) = limits
+        .check(&config.topology)
+        .and_then(|()| validate_config(&config, warnings, fail_fast))
+    {
+        tracing::error!("Invalid config, keeping the previous one: {:#}", e);
+        return Ok(false);
+    }
 
-    tracing::info!("Example SCION testnet setup complete.");
+    if only_topology_changed(running, &config)? {
+        tracing::error!(
+            "Only the topology changed, which pocketscion 0.4 can not apply to the running \
+             simulator, keeping the previous config. Restart the configurator to apply it."
+        );
+        return Ok(false);
+    }
+
+    tracing::info!("Config is valid, restarting to apply it...");
+    Ok(true)
+}
+
+/// Whether `new` differs from `old` in the topology and nowhere else
+fn only_topology_changed(old: &PocketScionConfig, new: &PocketScionConfig) -> anyhow::Result<bool> {
+    let to_value = |config| serde_json::to_value(config).context("Failed to serialize config");
+    let (mut old, mut new) = (to_value(old)?, to_value(new)?);
+    let old_topology = old.as_object_mut().and_then(|old| old.remove("topology"));
+    let new_topology = new.as_object_mut().and_then(|new| new.remove("topology"));
+    Ok(old_topology != new_topology && old == new)
+}
+
+/// Replace the current process with a fresh instance of the configurator, started with the same
+/// arguments. Only returns on failure.
+#[cfg(unix)]
+fn re_exec() -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
 
-    let token = dummy_snap_token();
-    tracing::info!("Dummy SNAP token: {}", token);
+    let exe = std::env::current_exe().context("Failed to find the configurator executable")?;
+    let err = std::process::Command::new(&exe)
+        .args(std::env::args_os().skip(1))
+        .exec();
+    Err(err).context(format!("Failed to execute {}", exe.display()))
+}
+
+#[cfg(not(unix))]
+fn re_exec() -> anyhow::Result<()> {
+    anyhow::bail!("Reloading is only supported on unix")
+}
+
+/// A signal that controls the running simulator
+enum ControlSignal {
+    /// Stop the simulator, with the name of the received signal
+    Shutdown(&'static str),
+    /// Re-read the config and apply it, with the reason for reloading
+    Reload(&'static str),
+}
+
+/// Listens for SIGINT (Ctrl-C) and, on unix, SIGTERM to shut down and SIGHUP to reload. With a
+/// watcher, a change of the config files is a reload as well.
+struct ControlSignals {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    watcher: Option<watch::ConfigWatcher>,
+}
 
-    // store token on disk
-    std::fs::write(&cli.token_file, token)
-        .context(format!("Failed to write SNAP token to {}", cli.token_file))?;
-    tracing::info!("Dummy SNAP token written to '{}'", cli.token_file);
+impl ControlSignals {
+    fn new(watcher: Option<watch::ConfigWatcher>) -> anyhow::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
 
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            tracing::info!("Received shutdown signal, stopping...");
+            Ok(Self {
+                terminate: signal(SignalKind::terminate())
+                    .context("Failed to listen for SIGTERM")?,
+                hangup: signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?,
+                watcher,
+            })
         }
+
+        #[cfg(not(unix))]
+        Ok(Self { watcher })
     }
 
+    /// Wait for the next control signal
+    async fn recv(&mut self) -> anyhow::Result<ControlSignal> {
+        let watcher = &mut self.watcher;
+        let config_changed = async move {
+            match watcher {
+                Some(watcher) => watcher.changed().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                res = tokio::signal::ctrl_c() => res
+                    .context("Failed to listen for SIGINT")
+                    .map(|_| ControlSignal::Shutdown("SIGINT")),
+                _ = self.terminate.recv() => Ok(ControlSignal::Shutdown("SIGTERM")),
+                _ = self.hangup.recv() => Ok(ControlSignal::Reload("Received SIGHUP")),
+                _ = config_changed => Ok(ControlSignal::Reload("Config file changed")),
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            tokio::select! {
+                res = tokio::signal::ctrl_c() => res
+                    .context("Failed to listen for Ctrl-C")
+                    .map(|_| ControlSignal::Shutdown("Ctrl-C")),
+                _ = config_changed => Ok(ControlSignal::Reload("Config file changed")),
+            }
+        }
+    }
+}
+
+/// Write an example config to `path`, or to stdout if `path` is `-`
+fn generate(path: &str, force: bool) -> anyhow::Result<()> {
+    write_config(path, &example_config(), force)
+}
+
+/// Print the JSON Schema of the config to stdout
+fn print_schema() -> anyhow::Result<()> {
+    let schema = serde_json::to_string_pretty(&schema::config_schema())
+        .context("Failed to serialize JSON Schema")?;
+    println!("{schema}");
+    Ok(())
+}
+
+/// Print a table of the ASes of the config at `config_paths`, or a JSON array of their summaries
+fn inspect(
+    config_paths: &[String],
+    load_options: &LoadOptions,
+    output_format: OutputFormat,
+) -> anyhow::Result<()> {
+    let config = load_configs(config_paths, load_options).map_err(|e| {
+        let failure = Failure::of_load_error(&e);
+        e.context(failure)
+    })?;
+    match output_format {
+        OutputFormat::Text => {
+            let table = inspect::as_table(&config).context(Failure::InvalidConfig)?;
+            print!("{table}");
+        }
+        OutputFormat::Json => {
+            let summaries = inspect::as_summaries(&config).context(Failure::InvalidConfig)?;
+            print_json(&summaries)?;
+        }
+    }
     Ok(())
 }
 
-/// Build a topology from the config structure
-fn build_topology_from_config(config: &TopologyConfig) -> anyhow::Result<ScionTopology> {
-    let mut topo = ScionTopology::new();
+/// Print `value` as pretty JSON to stdout
+fn print_json(value: &impl serde::Serialize) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value).context("Failed to serialize output")?;
+    println!("{json}");
+    Ok(())
+}
 
-    // Add all ASes
-    for as_config in &config.ases {
-        let isd_asn: IsdAsn = as_config.isd_as.parse()?;
-        if as_config.is_core {
-            topo.add_as(ScionAs::new_core(isd_asn))?;
-        } else {
-            topo.add_as(ScionAs::new(isd_asn))?;
-        }
-    }
-
-    // Add all links
-    for link_str in &config.links {
-        topo.add_link(link_str.parse()?)?;
-    }
-
-    Ok(topo)
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct PocketScionConfig {
-    /// The SCION network topology being simulated
-    topology: TopologyConfig,
-    /// SCION Network Access Points (SNAP) for the server and client
-    #[serde(skip_serializing_if = "Option::is_none")]
-    snaps: Option<Vec<SnapConfig>>,
-    /// Optional endhost API configurations
-    #[serde(skip_serializing_if = "Option::is_none")]
-    endhost_apis: Option<Vec<EndhostApiConfig>>,
-    /// Optional router configurations
-    #[serde(skip_serializing_if = "Option::is_none")]
-    routers: Option<Vec<RouterConfig>>,
-    /// Management API listen address
-    management_listen_addr: SocketAddr,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct TopologyConfig {
-    /// List of ASes in the topology
-    ases: Vec<AsConfig>,
-    /// List of links between ASes
-    links: Vec<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct AsConfig {
-    /// ISD-AS identifier (e.g., "1-11")
-    isd_as: String,
-    /// Whether this AS is a core AS
-    is_core: bool,
-}
-
-/// SCION Network Access Point (SNAP) configuration
-#[derive(Debug, Serialize, Deserialize)]
-struct SnapConfig {
-    /// Listening address for the SNAP's control plane
-    listening_addr: SocketAddr,
-    /// This SNAP's data plane
-    data_plane: DataPlaneConfig,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct DataPlaneConfig {
-    /// ISD-AS identifier for this data plane
-    isd_as: String,
-    /// The LAN address this data plane should listen on
-    listening_addr: SocketAddr,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct EndhostApiConfig {
-    /// ISDs this endhost API serves
-    isds: Vec<String>,
-    /// Listening address for the endhost API
-    listening_addr: SocketAddr,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct RouterConfig {
-    /// ISD-AS identifier
-    isd_as: String,
-    /// Interface IDs
-    interfaces: Vec<u16>,
-    /// Listening address
-    listening_addr: SocketAddr,
-    /// SNAP data plane exclude addresses
-    #[serde(default)]
-    snap_data_plane_excludes: Vec<IpNet>,
-    /// SNAP data plane interfaces
-    #[serde(default)]
-    snap_data_plane_interfaces: BTreeMap<String, SocketAddr>,
+/// Print a path from `src` to `dst` in the topology of the config at `config_paths`, or fail if
+/// there is none. The JSON output is printed in both cases.
+fn reachable(
+    config_paths: &[String],
+    load_options: &LoadOptions,
+    src: &str,
+    dst: &str,
+    output_format: OutputFormat,
+) -> anyhow::Result<()> {
+    let config = load_configs(config_paths, load_options).map_err(|e| {
+        let failure = Failure::of_load_error(&e);
+        e.context(failure)
+    })?;
+    let src = parse_isd_as(src, "the source AS").context(Failure::InvalidConfig)?;
+    let dst = parse_isd_as(dst, "the destination AS").context(Failure::InvalidConfig)?;
+    let path =
+        reachability::find_path(&config.topology, src, dst).context(Failure::InvalidConfig)?;
+    let path: Option<Vec<String>> = path.map(|path| path.iter().map(ToString::to_string).collect());
+    if output_format == OutputFormat::Json {
+        print_json(&reachability::Reachability {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            path: path.clone(),
+        })?;
+    }
+    match path {
+        Some(path) => {
+            if output_format == OutputFormat::Text {
+                println!("{src} can reach {dst}: {}", path.join(" -> "));
+            }
+            Ok(())
+        }
+        None => {
+            Err(anyhow::anyhow!("No SCION path from {src} to {dst}").context(Failure::Unreachable))
+        }
+    }
+}
+
+/// Print the semantic differences between the configs at `old` and `new`
+fn diff(
+    old: &str,
+    new: &str,
+    load_options: &LoadOptions,
+    output_format: OutputFormat,
+) -> anyhow::Result<()> {
+    let load = |path: &str| {
+        load_configs(&[path.to_string()], load_options).map_err(|e| {
+            let failure = Failure::of_load_error(&e);
+            e.context(failure)
+        })
+    };
+    let diff = diff::diff_configs(&load(old)?, &load(new)?).context(Failure::InvalidConfig)?;
+    match output_format {
+        OutputFormat::Text => print!("{diff}"),
+        OutputFormat::Json => print_json(&diff)?,
+    }
+    Ok(())
+}
+
+/// Import SCION topology files into a config skeleton and write it to `path`, or to stdout if
+/// `path` is `-`
+fn import(path: &str, scion_topologies: &[String], force: bool) -> anyhow::Result<()> {
+    let topology = import::import_scion_topologies(scion_topologies)?;
+    build_topology_from_config(&topology).context("Imported topology is invalid")?;
+
+    let config = PocketScionConfig {
+        includes: Vec::new(),
+        sources: Vec::new(),
+        topology,
+        snaps: None,
+        endhost_apis: None,
+        routers: None,
+        management_listen_addr: Some(SocketAddr::from(([127, 0, 0, 1], 8082))),
+        metrics_listen_addr: None,
+        health_listen_addr: None,
+        control_listen_addr: None,
+        control_auth_token: None,
+        logging: None,
+    };
+    write_config(path, &config, force)
+}
+
+/// Write the config at `config_paths` with its non-core ASes replicated `factor` times to `output`
+fn scale(
+    config_paths: &[String],
+    load_options: &LoadOptions,
+    factor: u32,
+    output: &str,
+    force: bool,
+) -> anyhow::Result<()> {
+    let mut config = load_configs(config_paths, load_options).map_err(|e| {
+        let failure = Failure::of_load_error(&e);
+        e.context(failure)
+    })?;
+    config.topology =
+        scale::scale_topology(&config.topology, factor).context(Failure::InvalidConfig)?;
+    write_config(output, &config, force)
+}
+
+/// Write `config` as JSON to `path`, or to stdout if `path` is `-`. An existing file is only
+/// overwritten if `force` is set.
+fn write_config(path: &str, config: &PocketScionConfig, force: bool) -> anyhow::Result<()> {
+    let config = serde_json::to_string_pretty(config).context("Failed to serialize config")?;
+
+    if path == "-" {
+        println!("{config}");
+        return Ok(());
+    }
+
+    if !force && Path::new(path).exists() {
+        anyhow::bail!("Config file {path} already exists, use --force to overwrite it");
+    }
+    std::fs::write(path, config + "\n").context(format!("Failed to write config to {path}"))?;
+    tracing::info!("Config written to '{}'", path);
+
+    Ok(())
+}
+
+/// A small but complete config: two core ASes joined by a link, a SNAP in the first AS, and an
+/// endhost API and router in the second one
+fn example_config() -> PocketScionConfig {
+    let addr = |s: &str| -> SocketAddr { s.parse().expect("valid socket address") };
+
+    PocketScionConfig {
+        includes: Vec::new(),
+        sources: Vec::new(),
+        topology: TopologyConfig {
+            ases: vec![
+                AsConfig {
+                    isd_as: "1-1".to_string(),
+                    is_core: true,
+                    default_mtu: None,
+                    default_bandwidth_bps: None,
+                },
+                AsConfig {
+                    isd_as: "1-2".to_string(),
+                    is_core: true,
+                    default_mtu: None,
+                    default_bandwidth_bps: None,
+                },
+            ],
+            links: vec![LinkConfig::Plain("1-1#1 core 1-2#1".to_string())],
+        },
+        snaps: Some(vec![SnapConfig {
+            listening_addr: addr("127.0.0.1:10111"),
+            data_plane: DataPlaneConfig {
+                isd_as: "1-1".to_string(),
+                listening_addr: addr("127.0.0.1:10112"),
+            },
+            enabled: true,
+        }]),
+        endhost_apis: Some(vec![EndhostApiConfig {
+            isds: vec!["1-2".to_string()],
+            listening_addr: addr("127.0.0.1:10121"),
+            enabled: true,
+        }]),
+        routers: Some(vec![RouterConfig {
+            isd_as: "1-2".to_string(),
+            interfaces: vec![1],
+            listening_addr: addr("127.0.0.1:10122"),
+            snap_data_plane_excludes: vec![],
+            snap_data_plane_interfaces: BTreeMap::new(),
+            enabled: true,
+        }]),
+        management_listen_addr: Some(addr("127.0.0.1:8082")),
+        metrics_listen_addr: None,
+        health_listen_addr: None,
+        control_listen_addr: None,
+        control_auth_token: None,
+        logging: None,
+    }
+}
+
+/// Remove the SNAPs, endhost APIs and routers from `config` for --topology-only, and log which of
+/// them were present
+fn ignore_non_topology_sections(config: &mut PocketScionConfig) {
+    let ignored: Vec<&str> = [
+        (
+            "snaps",
+            config.snaps.take().is_some_and(|snaps| !snaps.is_empty()),
+        ),
+        (
+            "endhost_apis",
+            config
+                .endhost_apis
+                .take()
+                .is_some_and(|apis| !apis.is_empty()),
+        ),
+        (
+            "routers",
+            config
+                .routers
+                .take()
+                .is_some_and(|routers| !routers.is_empty()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(section, present)| present.then_some(section))
+    .collect();
+
+    if ignored.is_empty() {
+        tracing::info!("Starting the topology only");
+    } else {
+        tracing::info!(
+            "Starting the topology only, ignoring the {} of the config",
+            ignored.join(", ")
+        );
+    }
+}
+
+/// Print a short summary of the config to stdout
+fn print_config_summary(config: &PocketScionConfig) {
+    println!("Config is valid:");
+    println!("  ASes:         {}", config.topology.ases.len());
+    println!("  Links:        {}", config.topology.links.len());
+    println!("  SNAPs:        {}", config.enabled_snaps().count());
+    println!("  Endhost APIs: {}", config.enabled_endhost_apis().count());
+    println!("  Routers:      {}", config.enabled_routers().count());
 }
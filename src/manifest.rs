@@ -0,0 +1,120 @@
+//! Manifest of a running simulator, with the identifiers pocketscion assigned to its components
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use pocketscion::api::admin::client::ApiClient;
+use pocketscion::endhost_api::EndhostApiId;
+use pocketscion::state::RouterId;
+use pocketscion::state::snap::SnapId;
+use scion_proto::address::IsdAsn;
+use serde::Serialize;
+
+use crate::{DEFAULT_MANAGEMENT_LISTEN_ADDR, PocketScionConfig};
+
+/// The components of a running simulator, as written to the `--manifest-file`
+#[derive(Serialize)]
+pub struct Manifest {
+    management_addr: SocketAddr,
+    snaps: Vec<SnapManifest>,
+    endhost_apis: Vec<EndhostApiManifest>,
+    routers: Vec<RouterManifest>,
+}
+
+#[derive(Serialize)]
+struct SnapManifest {
+    id: SnapId,
+    /// Position of the SNAP in the `snaps` list of the config
+    config_index: usize,
+    control_plane_addr: String,
+    data_planes: Vec<DataPlaneManifest>,
+}
+
+#[derive(Serialize)]
+struct DataPlaneManifest {
+    isd_as: String,
+    listen_addr: String,
+}
+
+#[derive(Serialize)]
+struct EndhostApiManifest {
+    id: EndhostApiId,
+    local_ases: BTreeSet<IsdAsn>,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct RouterManifest {
+    id: RouterId,
+    isd_as: IsdAsn,
+    addr: SocketAddr,
+}
+
+impl Manifest {
+    /// Ask the management API of the simulator started with `config` for its components.
+    /// pocketscion numbers the SNAPs in the order of the config, which gives the ISD-AS of
+    /// their data planes.
+    pub async fn collect(client: &ApiClient, config: &PocketScionConfig) -> anyhow::Result<Self> {
+        let io_config = client
+            .get_io_config()
+            .await
+            .context("Failed to get the IO config from the management API")?;
+        let routers = client
+            .get_routers()
+            .await
+            .context("Failed to get the routers from the management API")?;
+        let endhost_apis = client
+            .get_endhost_apis()
+            .await
+            .context("Failed to get the endhost APIs from the management API")?;
+
+        let snaps = io_config
+            .snaps
+            .into_iter()
+            .zip(config.enabled_snaps())
+            .map(|((id, io), (config_index, snap))| SnapManifest {
+                id,
+                config_index,
+                control_plane_addr: io.control_plane.api_addr.unwrap_or_default(),
+                data_planes: vec![DataPlaneManifest {
+                    isd_as: snap.data_plane.isd_as.clone(),
+                    listen_addr: io.data_plane.addr.unwrap_or_default(),
+                }],
+            })
+            .collect();
+
+        Ok(Self {
+            management_addr: config
+                .management_listen_addr
+                .unwrap_or(DEFAULT_MANAGEMENT_LISTEN_ADDR),
+            snaps,
+            endhost_apis: endhost_apis
+                .endhost_apis
+                .into_values()
+                .map(|api| EndhostApiManifest {
+                    id: api.id,
+                    local_ases: api.local_ases,
+                    url: api.url.to_string(),
+                })
+                .collect(),
+            routers: routers
+                .routers
+                .into_iter()
+                .map(|(id, router)| RouterManifest {
+                    id,
+                    isd_as: router.isd_as,
+                    addr: router.addr,
+                })
+                .collect(),
+        })
+    }
+
+    /// Write the manifest as JSON to `path`
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize manifest")?;
+        std::fs::write(path, json + "\n").context(format!("Failed to write manifest to {path}"))?;
+        tracing::info!("Manifest written to '{}'", path);
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Prometheus metrics describing the simulated network
+
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use prometheus::{IntGauge, Registry, TextEncoder};
+use tokio::task::JoinHandle;
+
+use crate::PocketScionConfig;
+
+/// Gauges describing the network of the running config and when the simulator was started
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    ases: IntGauge,
+    links: IntGauge,
+    snaps: IntGauge,
+    data_planes: IntGauge,
+    endhost_apis: IntGauge,
+    routers: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("pocketscion".to_string()), None)
+            .context("Failed to create metrics registry")?;
+
+        let gauge = |name: &str, help: &str| -> anyhow::Result<IntGauge> {
+            let gauge = IntGauge::new(name, help)?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let ases = gauge("ases", "Number of ASes in the topology")?;
+        let links = gauge("links", "Number of links in the topology")?;
+        let snaps = gauge("snaps", "Number of SNAPs")?;
+        let data_planes = gauge("data_planes", "Number of SNAP data planes")?;
+        let endhost_apis = gauge("endhost_apis", "Number of endhost APIs")?;
+        let routers = gauge("routers", "Number of routers")?;
+
+        let start_time = gauge(
+            "start_time_seconds",
+            "Start time of the simulator in seconds since the Unix epoch",
+        )?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        start_time.set(now.as_secs().try_into().unwrap_or(i64::MAX));
+
+        Ok(Self {
+            registry,
+            ases,
+            links,
+            snaps,
+            data_planes,
+            endhost_apis,
+            routers,
+        })
+    }
+
+    /// Set the gauges to describe the network of `config`
+    pub fn set_config(&self, config: &PocketScionConfig) {
+        let count = |n: usize| n.try_into().unwrap_or(i64::MAX);
+        let snaps = config.enabled_snaps().count();
+
+        self.ases.set(count(config.topology.ases.len()));
+        self.links.set(count(config.topology.links.len()));
+        self.snaps.set(count(snaps));
+        // Every SNAP has exactly one data plane
+        self.data_planes.set(count(snaps));
+        self.endhost_apis
+            .set(count(config.enabled_endhost_apis().count()));
+        self.routers.set(count(config.enabled_routers().count()));
+    }
+
+    /// Encode all metrics in the Prometheus text format
+    fn encode(&self) -> anyhow::Result<String> {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .context("Failed to encode metrics")
+    }
+}
+
+/// Serve the metrics on `/metrics` at `addr` until the returned task is aborted, and return the
+/// bound address with the task
+pub async fn serve(
+    addr: SocketAddr,
+    metrics: Metrics,
+) -> anyhow::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .context(format!("Failed to bind metrics listener to {addr}"))?;
+    let addr = listener
+        .local_addr()
+        .context("Failed to get the metrics listen address")?;
+    tracing::info!(metrics_listen_addr = %addr, "Serving metrics on /metrics");
+
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics);
+
+    let server = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("Metrics server failed: {}", e);
+        }
+    });
+    Ok((addr, server))
+}
+
+async fn metrics_handler(State(metrics): State<Metrics>) -> impl IntoResponse {
+    match metrics.encode() {
+        Ok(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+            body,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")).into_response(),
+    }
+}
@@ -0,0 +1,367 @@
+//! Free ports for listen addresses configured with port 0
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Context;
+use pocketscion::api::admin::client::ApiClient;
+use serde::Serialize;
+
+use crate::{ConfigWarnings, DEFAULT_MANAGEMENT_LISTEN_ADDR, PocketScionConfig};
+
+/// Transport protocol of a listen address
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// The listen addresses of a running simulator, as written to the `--print-ports-file`
+#[derive(Serialize)]
+pub struct ListenAddrs {
+    management: SocketAddr,
+    snaps: Vec<SnapListenAddrs>,
+    endhost_apis: Vec<SocketAddr>,
+    routers: Vec<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    control: Option<SocketAddr>,
+}
+
+#[derive(Serialize)]
+struct SnapListenAddrs {
+    control_plane: SocketAddr,
+    data_plane: SocketAddr,
+}
+
+/// Replace port 0 of the management API and SNAP listen addresses in `config` with a port that
+/// is currently free.
+///
+/// pocketscion binds port 0 as given, but keeps reporting port 0 to SNAP clients and in its
+/// management API, and does not expose the address of the management API, so these ports are
+/// picked here before the simulator is started. Endhost APIs and routers get their port from
+/// pocketscion, which reports it in the management API, and the metrics, health and control
+/// servers of the configurator log the port they bind.
+///
+/// A picked port is only free until the simulator binds it. Another process can take it in
+/// between, which makes the startup fail with an address in use error.
+pub fn resolve_ephemeral_ports(config: &mut PocketScionConfig) -> anyhow::Result<()> {
+    let management = config
+        .management_listen_addr
+        .get_or_insert(DEFAULT_MANAGEMENT_LISTEN_ADDR);
+    resolve(management, Protocol::Tcp, "management API")?;
+
+    for (index, snap) in config.snaps.iter_mut().flatten().enumerate() {
+        if !snap.enabled {
+            continue;
+        }
+        let name = format!("SNAP {index}");
+        resolve(
+            &mut snap.listening_addr,
+            Protocol::Tcp,
+            &format!("{name} control plane"),
+        )?;
+        resolve(
+            &mut snap.data_plane.listening_addr,
+            Protocol::Udp,
+            &format!("{name} data plane"),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Check that no two listeners of the same protocol use the same port on overlapping addresses,
+/// where an unspecified address like `0.0.0.0` overlaps with every address of its family.
+/// Listeners with port 0 are skipped, they get distinct ports.
+pub fn check_conflicts(config: &PocketScionConfig) -> anyhow::Result<()> {
+    let listeners = listeners(config);
+    let mut conflicts = Vec::new();
+    for (index, (name, protocol, addr)) in listeners.iter().enumerate() {
+        for (other_name, other_protocol, other_addr) in &listeners[index + 1..] {
+            if protocol == other_protocol && addr.port() != 0 && overlap(addr, other_addr) {
+                conflicts.push(format!("  {name} ({addr}) and {other_name} ({other_addr})"));
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        anyhow::bail!(
+            "Listen addresses conflict with each other:\n{}",
+            conflicts.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Bind every listen address of `config` for a moment, and report all addresses that can not be
+/// bound in a single error. pocketscion stops at the first address it can not bind and leaves the
+/// addresses it bound before that in use.
+pub fn check_bindable(config: &PocketScionConfig) -> anyhow::Result<()> {
+    let unavailable: Vec<String> = listeners(config)
+        .into_iter()
+        .filter_map(|(name, protocol, addr)| {
+            bind_error(protocol, addr).map(|e| format!("  {name} ({addr}): {e}"))
+        })
+        .collect();
+
+    if !unavailable.is_empty() {
+        anyhow::bail!(
+            "Listen addresses can not be bound:\n{}",
+            unavailable.join("\n")
+        );
+    }
+    tracing::info!("All listen addresses can be bound");
+    Ok(())
+}
+
+/// Disable the SNAPs, endhost APIs and routers whose listen addresses can not be bound, logging
+/// each of them, so that the simulator starts with the others. The addresses are only probed, so
+/// one that is taken right after the probe still fails the startup.
+pub fn disable_unbindable(config: &mut PocketScionConfig) {
+    fn disable<T>(
+        list: &mut Option<Vec<T>>,
+        name: &str,
+        enabled: impl Fn(&mut T) -> &mut bool,
+        addrs: impl Fn(&T) -> Vec<(Protocol, SocketAddr)>,
+    ) -> String {
+        let (mut total, mut started) = (0, 0);
+        for (index, entry) in list.iter_mut().flatten().enumerate() {
+            if !*enabled(entry) {
+                continue;
+            }
+            total += 1;
+            let error = addrs(entry)
+                .into_iter()
+                .find_map(|(protocol, addr)| bind_error(protocol, addr).map(|e| (addr, e)));
+            match error {
+                Some((addr, e)) => {
+                    tracing::warn!("Skipping {name}[{index}], {addr} can not be bound: {e}");
+                    *enabled(entry) = false;
+                }
+                None => started += 1,
+            }
+        }
+        format!("{started} of {total} {name}")
+    }
+
+    let snaps = disable(
+        &mut config.snaps,
+        "snaps",
+        |snap| &mut snap.enabled,
+        |snap| {
+            vec![
+                (Protocol::Tcp, snap.listening_addr),
+                (Protocol::Udp, snap.data_plane.listening_addr),
+            ]
+        },
+    );
+    let endhost_apis = disable(
+        &mut config.endhost_apis,
+        "endhost_apis",
+        |api| &mut api.enabled,
+        |api| vec![(Protocol::Tcp, api.listening_addr)],
+    );
+    let routers = disable(
+        &mut config.routers,
+        "routers",
+        |router| &mut router.enabled,
+        |router| vec![(Protocol::Udp, router.listening_addr)],
+    );
+    tracing::info!("Starting {}, {} and {}", snaps, endhost_apis, routers);
+}
+
+/// The error of binding `addr`, if it can not be bound right now
+fn bind_error(protocol: Protocol, addr: SocketAddr) -> Option<std::io::Error> {
+    let bound = match protocol {
+        Protocol::Tcp => std::net::TcpListener::bind(addr).map(drop),
+        Protocol::Udp => std::net::UdpSocket::bind(addr).map(drop),
+    };
+    bound.err()
+}
+
+/// Report listen IPs that are not an address of this host, a common copy-paste error that would
+/// otherwise only show up as a bind error on startup. Unspecified and loopback IPs are skipped.
+/// Every IP is probed by binding a UDP socket to a free port on it.
+pub fn check_interfaces(
+    config: &PocketScionConfig,
+    warnings: ConfigWarnings,
+) -> anyhow::Result<()> {
+    let mut by_ip: BTreeMap<IpAddr, (SocketAddr, Vec<String>)> = BTreeMap::new();
+    for (name, _, addr) in listeners(config) {
+        if addr.ip().is_unspecified() || addr.ip().is_loopback() {
+            continue;
+        }
+        by_ip
+            .entry(addr.ip())
+            .or_insert_with(|| (addr, Vec::new()))
+            .1
+            .push(name);
+    }
+
+    for (ip, (mut probe, names)) in by_ip {
+        // Keeps the scope ID of a link-local IPv6 address
+        probe.set_port(0);
+        if let Err(e) = std::net::UdpSocket::bind(probe)
+            && e.kind() == std::io::ErrorKind::AddrNotAvailable
+        {
+            warnings.report(format!(
+                "{ip} is not an address of this host, but {} listen on it",
+                names.join(", ")
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether two listeners can not both bind their address. An unspecified IPv6 address also
+/// accepts IPv4 on a dual-stack host, which is the default on Linux, so it overlaps every IPv4
+/// address.
+fn overlap(a: &SocketAddr, b: &SocketAddr) -> bool {
+    let unspecified_v6 = |addr: &SocketAddr| addr.is_ipv6() && addr.ip().is_unspecified();
+    a.port() == b.port()
+        && (a.ip() == b.ip()
+            || if a.is_ipv4() == b.is_ipv4() {
+                a.ip().is_unspecified() || b.ip().is_unspecified()
+            } else {
+                unspecified_v6(a) || unspecified_v6(b)
+            })
+}
+
+/// Every listen address of the config, with a name for messages
+fn listeners(config: &PocketScionConfig) -> Vec<(String, Protocol, SocketAddr)> {
+    let mut listeners = vec![(
+        "management API".to_string(),
+        Protocol::Tcp,
+        config
+            .management_listen_addr
+            .unwrap_or(DEFAULT_MANAGEMENT_LISTEN_ADDR),
+    )];
+    for (index, snap) in config.enabled_snaps() {
+        listeners.push((
+            format!("SNAP {index} control plane"),
+            Protocol::Tcp,
+            snap.listening_addr,
+        ));
+        listeners.push((
+            format!("SNAP {index} data plane"),
+            Protocol::Udp,
+            snap.data_plane.listening_addr,
+        ));
+    }
+    for (index, api) in config.enabled_endhost_apis() {
+        listeners.push((
+            format!("endhost API {index}"),
+            Protocol::Tcp,
+            api.listening_addr,
+        ));
+    }
+    for (index, router) in config.enabled_routers() {
+        listeners.push((
+            format!("router {index} of AS {}", router.isd_as),
+            Protocol::Udp,
+            router.listening_addr,
+        ));
+    }
+    let servers = [
+        ("metrics", config.metrics_listen_addr),
+        ("health checks", config.health_listen_addr),
+        ("control API", config.control_listen_addr),
+    ];
+    for (name, addr) in servers {
+        if let Some(addr) = addr {
+            listeners.push((name.to_string(), Protocol::Tcp, addr));
+        }
+    }
+    listeners
+}
+
+/// Replace port 0 of `addr` with a free port and log it
+fn resolve(addr: &mut SocketAddr, protocol: Protocol, name: &str) -> anyhow::Result<()> {
+    if addr.port() != 0 {
+        return Ok(());
+    }
+
+    let free = match protocol {
+        Protocol::Tcp => std::net::TcpListener::bind(*addr).and_then(|l| l.local_addr()),
+        Protocol::Udp => std::net::UdpSocket::bind(*addr).and_then(|s| s.local_addr()),
+    }
+    .context(format!(
+        "Failed to find a free port on {addr} for the {name}"
+    ))?;
+    tracing::info!(addr = %free, "Using free port {} for the {}", free.port(), name);
+
+    *addr = free;
+    Ok(())
+}
+
+impl ListenAddrs {
+    /// The listen addresses of the simulator started with `config`, whose port 0 of the
+    /// management API and SNAPs has been resolved, and of the servers of the configurator once
+    /// they are bound. The addresses of the endhost APIs and routers are asked from the
+    /// management API.
+    pub async fn collect(client: &ApiClient, config: &PocketScionConfig) -> anyhow::Result<Self> {
+        let io_config = client
+            .get_io_config()
+            .await
+            .context("Failed to get the IO config from the management API")?;
+        let parse = |addr: String| {
+            addr.parse::<SocketAddr>()
+                .context(format!("Invalid listen address {addr} in the IO config"))
+        };
+
+        Ok(Self {
+            management: config
+                .management_listen_addr
+                .unwrap_or(DEFAULT_MANAGEMENT_LISTEN_ADDR),
+            snaps: config
+                .enabled_snaps()
+                .map(|(_, snap)| SnapListenAddrs {
+                    control_plane: snap.listening_addr,
+                    data_plane: snap.data_plane.listening_addr,
+                })
+                .collect(),
+            endhost_apis: io_config
+                .endhost_apis
+                .into_values()
+                .map(parse)
+                .collect::<anyhow::Result<_>>()?,
+            routers: io_config
+                .router_sockets
+                .into_values()
+                .map(parse)
+                .collect::<anyhow::Result<_>>()?,
+            metrics: config.metrics_listen_addr,
+            health: config.health_listen_addr,
+            control: config.control_listen_addr,
+        })
+    }
+
+    /// Write the addresses as JSON to `path`
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize ports")?;
+        std::fs::write(path, json + "\n").context(format!("Failed to write ports file {path}"))?;
+        tracing::info!("Listen addresses written to '{}'", path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unspecified_ipv6_overlaps_ipv4() {
+        let addr = |addr: &str| addr.parse::<SocketAddr>().unwrap();
+
+        assert!(overlap(&addr("[::]:8080"), &addr("0.0.0.0:8080")));
+        assert!(overlap(&addr("127.0.0.1:8080"), &addr("[::]:8080")));
+        assert!(overlap(&addr("0.0.0.0:8080"), &addr("127.0.0.1:8080")));
+        assert!(!overlap(&addr("[::]:8080"), &addr("0.0.0.0:8081")));
+        assert!(!overlap(&addr("[::1]:8080"), &addr("0.0.0.0:8080")));
+        assert!(!overlap(&addr("[::1]:8080"), &addr("127.0.0.1:8080")));
+    }
+}
@@ -0,0 +1,116 @@
+//! Whether a SCION path can exist between two ASes of a topology, without running beaconing
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use anyhow::Context;
+use pocketscion::network::scion::topology::{ScionLink, ScionLinkType};
+use scion_proto::address::IsdAsn;
+use serde::Serialize;
+
+use crate::{TopologyConfig, build_topology_from_config, link_ases, parse_isd_as};
+
+/// Whether `dst` can be reached from `src`, with the ASes of a path if it can
+#[derive(Serialize)]
+pub struct Reachability {
+    pub src: String,
+    pub dst: String,
+    pub path: Option<Vec<String>>,
+}
+
+/// The segment of a path an AS is reached in: a path goes up from the source to a core AS,
+/// along core links and down to the destination, and each part may be empty
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Up,
+    Core,
+    Down,
+}
+
+/// Find a path from `src` to `dst` that follows the SCION segment rules: up along child-parent
+/// links, across core links between core ASes and down along parent-child links. Like in SCION,
+/// a path may also turn down at an AS of the up segment, or cross a peering link from the up
+/// to the down segment. Returns the ASes of the path, or `None` if there is no path.
+pub fn find_path(
+    config: &TopologyConfig,
+    src: IsdAsn,
+    dst: IsdAsn,
+) -> anyhow::Result<Option<Vec<IsdAsn>>> {
+    build_topology_from_config(config)?;
+
+    let mut core_ases = BTreeSet::new();
+    let mut declared_ases = BTreeSet::new();
+    for (index, as_config) in config.ases.iter().enumerate() {
+        let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        declared_ases.insert(isd_as);
+        if as_config.is_core {
+            core_ases.insert(isd_as);
+        }
+    }
+    for isd_as in [src, dst] {
+        if !declared_ases.contains(&isd_as) {
+            anyhow::bail!("AS {isd_as} is not declared in the topology");
+        }
+    }
+
+    // The neighbors of every AS, with the link type from the perspective of the AS
+    let mut neighbors: BTreeMap<IsdAsn, Vec<(IsdAsn, ScionLinkType)>> = BTreeMap::new();
+    for link_config in &config.links {
+        let link_str = link_config.link();
+        let link: ScionLink = link_str
+            .parse()
+            .with_context(|| format!("Invalid link '{link_str}'"))?;
+        let [from, to] = link_ases(&link_str)?;
+        for (this, other) in [(from, to), (to, from)] {
+            if let Some(link_type) = link.get_link_type(&this) {
+                neighbors.entry(this).or_default().push((other, link_type));
+            }
+        }
+    }
+
+    let start = (src, Segment::Up);
+    let mut previous: BTreeMap<(IsdAsn, Segment), (IsdAsn, Segment)> = BTreeMap::new();
+    let mut visited = BTreeSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(state @ (isd_as, segment)) = queue.pop_front() {
+        if isd_as == dst {
+            let mut path = vec![isd_as];
+            let mut current = state;
+            while let Some(&before) = previous.get(&current) {
+                if before.0 != current.0 {
+                    path.push(before.0);
+                }
+                current = before;
+            }
+            path.reverse();
+            return Ok(Some(path));
+        }
+
+        let mut next = Vec::new();
+        match segment {
+            Segment::Up => {
+                if core_ases.contains(&isd_as) {
+                    next.push((isd_as, Segment::Core));
+                }
+                next.push((isd_as, Segment::Down));
+            }
+            Segment::Core => next.push((isd_as, Segment::Down)),
+            Segment::Down => {}
+        }
+        for &(neighbor, link_type) in neighbors.get(&isd_as).into_iter().flatten() {
+            match (segment, link_type) {
+                (Segment::Up, ScionLinkType::Child) => next.push((neighbor, Segment::Up)),
+                (Segment::Up, ScionLinkType::Peer) => next.push((neighbor, Segment::Down)),
+                (Segment::Core, ScionLinkType::Core) => next.push((neighbor, Segment::Core)),
+                (Segment::Down, ScionLinkType::Parent) => next.push((neighbor, Segment::Down)),
+                _ => {}
+            }
+        }
+        for state_after in next {
+            if visited.insert(state_after) {
+                previous.insert(state_after, state);
+                queue.push_back(state_after);
+            }
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,215 @@
+//! Scaling a topology up by replicating its non-core ASes, for load tests of the simulator
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use scion_proto::address::{Asn, Isd, IsdAsn};
+
+use crate::{
+    AsConfig, LinkConfig, TopologyConfig, build_topology_from_config, link_ends, parse_isd_as,
+};
+
+/// An end of a link, the AS and its interface
+type LinkEnd = (IsdAsn, u16);
+
+/// Replicate the non-core ASes of `topology` so that there are `factor` copies of each, the
+/// original and `factor - 1` replicas. Replicas get the next free AS numbers of their ISD, the
+/// link defaults of their original and keep the links among each other. Links to core ASes are
+/// added again for every replica with the next free interface IDs of the core AS, links between
+/// core ASes are kept once.
+pub fn scale_topology(topology: &TopologyConfig, factor: u32) -> anyhow::Result<TopologyConfig> {
+    if factor == 0 {
+        anyhow::bail!("The scale factor must be at least 1");
+    }
+    build_topology_from_config(topology)?;
+
+    let mut ases = BTreeMap::new();
+    let mut next_asn: BTreeMap<Isd, u64> = BTreeMap::new();
+    for (index, as_config) in topology.ases.iter().enumerate() {
+        let isd_as = parse_isd_as(&as_config.isd_as, &format!("topology.ases[{index}]"))?;
+        ases.insert(isd_as, as_config);
+        let next = next_asn.entry(isd_as.isd()).or_default();
+        *next = (*next).max(isd_as.asn().to_u64() + 1);
+    }
+
+    let mut links = Vec::new();
+    let mut next_interface: BTreeMap<IsdAsn, u16> = BTreeMap::new();
+    for link_config in &topology.links {
+        // With a relationship, the link type of a link object is only in the link string
+        let link_str = link_config.link();
+        let ends = link_ends(&link_str)?;
+        let link_type = link_str
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        for (isd_as, interface) in ends {
+            let next = next_interface.entry(isd_as).or_default();
+            *next = (*next).max(interface.saturating_add(1));
+        }
+        links.push((link_config, ends, link_type));
+    }
+
+    let mut scaled = topology.clone();
+    for replica in 1..factor {
+        let mut replica_ases = BTreeMap::new();
+        for (&isd_as, as_config) in ases.iter().filter(|(_, as_config)| !as_config.is_core) {
+            let next = next_asn.entry(isd_as.isd()).or_default();
+            if *next > Asn::MAX.to_u64() {
+                anyhow::bail!(
+                    "ISD {} has no AS numbers left for replica {replica}",
+                    isd_as.isd()
+                );
+            }
+            let replica_as = IsdAsn::new(isd_as.isd(), Asn::new(*next));
+            *next += 1;
+            replica_ases.insert(isd_as, replica_as);
+            scaled.ases.push(AsConfig {
+                isd_as: replica_as.to_string(),
+                ..(*as_config).clone()
+            });
+        }
+
+        for (link_config, ends, link_type) in &links {
+            if ends
+                .iter()
+                .all(|(isd_as, _)| ases.get(isd_as).is_some_and(|as_config| as_config.is_core))
+            {
+                continue;
+            }
+            let mut replica_ends = *ends;
+            for (isd_as, interface) in &mut replica_ends {
+                match replica_ases.get(isd_as) {
+                    Some(replica_as) => *isd_as = *replica_as,
+                    None => {
+                        let next = next_interface.entry(*isd_as).or_default();
+                        *interface = *next;
+                        *next = next.checked_add(1).with_context(|| {
+                            format!("AS {isd_as} has no interface IDs left for replica {replica}")
+                        })?;
+                    }
+                }
+            }
+            scaled
+                .links
+                .push(replica_link(link_config, replica_ends, link_type));
+        }
+    }
+
+    build_topology_from_config(&scaled).context("Scaled topology is invalid")?;
+    Ok(scaled)
+}
+
+/// A copy of `link_config` between other ends, with the same type and attributes
+fn replica_link(link_config: &LinkConfig, ends: [LinkEnd; 2], link_type: &str) -> LinkConfig {
+    let [(from, from_interface), (to, to_interface)] = ends;
+    let link = format!("{from}#{from_interface} {link_type} {to}#{to_interface}");
+    match link_config {
+        LinkConfig::Plain(_) => LinkConfig::Plain(link),
+        LinkConfig::Detailed(detailed) => {
+            let mut detailed = detailed.clone();
+            detailed.link = link;
+            LinkConfig::Detailed(detailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topology(value: serde_json::Value) -> TopologyConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn link_strings(topology: &TopologyConfig) -> Vec<String> {
+        topology
+            .links
+            .iter()
+            .map(|link_config| link_config.link().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn two_ases_scaled_by_three() {
+        let scaled = scale_topology(
+            &topology(serde_json::json!({
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-2", "is_core": false, "default_mtu": 1400 }
+                ],
+                "links": ["1-1#1 parent_of 1-2#1"]
+            })),
+            3,
+        )
+        .unwrap();
+
+        let ases: Vec<&str> = scaled
+            .ases
+            .iter()
+            .map(|as_config| as_config.isd_as.as_str())
+            .collect();
+        assert_eq!(ases, ["1-1", "1-2", "1-3", "1-4"]);
+        assert_eq!(scaled.ases[3].default_mtu, Some(1400));
+        assert_eq!(
+            link_strings(&scaled),
+            [
+                "1-1#1 parent_of 1-2#1",
+                "1-1#2 parent_of 1-3#1",
+                "1-1#3 parent_of 1-4#1"
+            ]
+        );
+    }
+
+    #[test]
+    fn core_links_are_kept_once_and_link_objects_get_new_interfaces() {
+        let scaled = scale_topology(
+            &topology(serde_json::json!({
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-2", "is_core": true },
+                    { "isd_as": "1-3", "is_core": false }
+                ],
+                "links": [
+                    "1-1#1 core 1-2#1",
+                    { "link": "1-1#5 1-3#1", "relationship": "parent", "latency_ms": 10 }
+                ]
+            })),
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            link_strings(&scaled),
+            [
+                "1-1#1 core 1-2#1",
+                "1-1#5 parent_of 1-3#1",
+                "1-1#6 parent_of 1-4#1"
+            ]
+        );
+        let replica = scaled.links[2].attributes().unwrap();
+        assert_eq!(replica.latency_ms, Some(10));
+    }
+
+    #[test]
+    fn running_out_of_as_numbers_is_an_error() {
+        let error = scale_topology(
+            &topology(serde_json::json!({
+                "ases": [
+                    { "isd_as": "1-1", "is_core": true },
+                    { "isd_as": "1-ffff:ffff:ffff", "is_core": false }
+                ],
+                "links": ["1-1#1 parent_of 1-ffff:ffff:ffff#1"]
+            })),
+            2,
+        )
+        .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("ISD 1 has no AS numbers left for replica 1"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! JSON Schema of the config, for completion and validation in editors. The schema is derived
+//! from the config types, and the types here describe the strings that the config parses itself.
+
+use std::borrow::Cow;
+
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+
+use crate::PocketScionConfig;
+
+/// The JSON Schema of [`PocketScionConfig`]. Draft 7 is used, as it is understood by most editors.
+pub fn config_schema() -> Schema {
+    SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<PocketScionConfig>()
+}
+
+/// `pattern`, or any string with a `${VAR}` reference, which is only replaced when the config is
+/// read
+fn pattern_or_var(pattern: &str) -> String {
+    format!("{pattern}|\\$\\{{[A-Za-z_][A-Za-z0-9_]*\\}}")
+}
+
+/// An ISD-AS identifier
+pub(crate) struct IsdAs;
+
+/// An IP address and port
+pub(crate) struct SocketAddress;
+
+/// An IP network in CIDR notation, or a single IP address
+pub(crate) struct IpNetwork;
+
+/// A link of the `links` format
+pub(crate) struct Link;
+
+impl JsonSchema for IsdAs {
+    fn schema_name() -> Cow<'static, str> {
+        "IsdAs".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "ISD-AS identifier, with the AS number in decimal or as three groups of hex digits",
+            "pattern": pattern_or_var("^[0-9]+-([0-9]+|[0-9a-fA-F]{1,4}:[0-9a-fA-F]{1,4}:[0-9a-fA-F]{1,4})$"),
+            "examples": ["1-1", "1-ff00:0:110"],
+        })
+    }
+}
+
+impl JsonSchema for SocketAddress {
+    fn schema_name() -> Cow<'static, str> {
+        "SocketAddress".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "IP address and port, IPv6 addresses are written in brackets, with an optional numeric scope ID for link-local addresses",
+            "pattern": pattern_or_var("^([0-9.]+|\\[[0-9a-fA-F:.]+(%[0-9]+)?\\]):[0-9]+$"),
+            "examples": ["127.0.0.1:8082", "[::1]:8082", "[fe80::1%2]:8082"],
+        })
+    }
+}
+
+impl JsonSchema for IpNetwork {
+    fn schema_name() -> Cow<'static, str> {
+        "IpNetwork".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "IP network in CIDR notation, or a single IP address",
+            "pattern": pattern_or_var("^[0-9a-fA-F:.]+(/[0-9]+)?$"),
+            "examples": ["10.0.0.0/8", "fd00::/8", "10.0.0.5"],
+        })
+    }
+}
+
+impl JsonSchema for Link {
+    fn schema_name() -> Cow<'static, str> {
+        "Link".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "Link between two AS interfaces: '<ISD-AS>#<interface> <type> <ISD-AS>#<interface>', where the type is one of peer, core, parent_of (or down_to) and child_of (or up_to)",
+            "pattern": pattern_or_var("^\\s*\\S+#[0-9]+\\s+(peer|core|parent_of|down_to|child_of|up_to)\\s+\\S+#[0-9]+\\s*$"),
+            "examples": ["1-1#1 core 1-2#1", "1-1#2 parent_of 1-3#1"],
+        })
+    }
+}
+
+/// The `link` of a link object, which may leave out the link type if the relationship is set
+pub(crate) fn detailed_link(generator: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            generator.subschema_for::<Link>(),
+            {
+                "type": "string",
+                "description": "The two interfaces of the link, with the link type given by relationship",
+                "pattern": pattern_or_var("^\\s*\\S+#[0-9]+\\s+\\S+#[0-9]+\\s*$"),
+                "examples": ["1-1#1 1-2#1"],
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// The properties that `schema` allows, following references and all branches of `anyOf`
+    /// and `allOf`
+    fn properties<'a>(schema: &'a Value, root: &'a Value) -> Vec<(&'a String, &'a Value)> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/definitions/");
+            return properties(&root["definitions"][name], root);
+        }
+        let mut properties: Vec<_> = schema["properties"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .collect();
+        for key in ["anyOf", "allOf"] {
+            for branch in schema[key].as_array().into_iter().flatten() {
+                properties.extend(self::properties(branch, root));
+            }
+        }
+        properties
+    }
+
+    /// Check that every field of the objects in `value` is a property of `schema`, or of a map
+    /// that allows any field, and the same for the values of the fields
+    fn assert_described(value: &Value, schema: &Value, root: &Value, path: &str) {
+        match value {
+            Value::Object(fields) => {
+                let properties = properties(schema, root);
+                let map_values = &schema["additionalProperties"];
+                for (field, value) in fields {
+                    let field_schema = match properties.iter().find(|(name, _)| *name == field) {
+                        Some((_, field_schema)) => field_schema,
+                        None if map_values.is_object() => map_values,
+                        None => panic!("{path}.{field} is not in the schema"),
+                    };
+                    assert_described(value, field_schema, root, &format!("{path}.{field}"));
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    assert_described(item, &schema["items"], root, &format!("{path}[{index}]"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_config_field_is_in_the_schema() {
+        let addr = "127.0.0.1:1";
+        let config: PocketScionConfig = serde_json::from_value(json!({
+            "includes": ["fragment.json"],
+            "topology": {
+                "ases": [{ "isd_as": "1-1", "is_core": true, "default_mtu": 1400,
+                           "default_bandwidth_bps": 1 }],
+                "links": [{ "link": "1-1#1 1-1#2", "relationship": "core", "latency_ms": 2,
+                            "jitter_ms": 1, "bandwidth_bps": 1, "mtu": 1400 }],
+            },
+            "snaps": [{ "listening_addr": addr, "data_plane": { "isd_as": "1-1", "listening_addr": addr },
+                        "enabled": false }],
+            "endhost_apis": [{ "isds": ["1-1"], "listening_addr": addr, "enabled": false }],
+            "routers": [{ "isd_as": "1-1", "interfaces": [1], "listening_addr": addr,
+                          "snap_data_plane_excludes": ["10.0.0.0/8"],
+                          "snap_data_plane_interfaces": { "1": addr }, "enabled": false }],
+            "management_listen_addr": addr,
+            "metrics_listen_addr": addr,
+            "health_listen_addr": addr,
+            "control_listen_addr": addr,
+            "control_auth_token": "token",
+            "logging": { "default_level": "info", "modules": { "pocketscion": "warn" } },
+        }))
+        .unwrap();
+        let value = serde_json::to_value(&config).unwrap();
+        let schema = config_schema().to_value();
+
+        assert_described(&value, &schema, &schema, "config");
+    }
+
+    #[test]
+    fn optional_fields_and_var_references_are_allowed() {
+        let schema = config_schema().to_value();
+
+        let addr_pattern = schema["definitions"]["SocketAddress"]["pattern"]
+            .as_str()
+            .unwrap();
+        assert!(
+            addr_pattern.ends_with("|\\$\\{[A-Za-z_][A-Za-z0-9_]*\\}"),
+            "{addr_pattern}"
+        );
+        assert_eq!(
+            schema["properties"]["metrics_listen_addr"]["anyOf"][1]["type"],
+            "null"
+        );
+        assert_eq!(
+            schema["definitions"]["AsConfig"]["properties"]["default_mtu"]["type"],
+            json!(["integer", "null"])
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! SNAP tokens signed with a key pair that is generated for every run of the simulator
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use ed25519_dalek::SigningKey;
+use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use pem::Pem;
+use snap_tokens::v0::{Pssid, SnapTokenClaims};
+
+/// How long issued tokens are valid, the same as for the dummy token
+const TOKEN_VALIDITY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Issues v0 SNAP tokens, each with its own pseudo SCION subscriber identity (PSSID). The v0
+/// claims do not name a SNAP, so every SNAP of the simulator accepts every token of the issuer,
+/// and the token files of the SNAPs only decide which client uses which PSSID.
+pub struct SnapTokenIssuer {
+    encoding_key: EncodingKey,
+    public_pem: Pem,
+}
+
+impl SnapTokenIssuer {
+    /// Create an issuer with a fresh random Ed25519 key pair
+    pub fn generate() -> anyhow::Result<Self> {
+        let signing_key = SigningKey::from_bytes(&rand::random());
+
+        let private_der = signing_key
+            .to_pkcs8_der()
+            .context("Failed to encode SNAP token signing key")?;
+        let encoding_key = EncodingKey::from_ed_der(private_der.as_bytes());
+
+        let public_der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .context("Failed to encode SNAP token verifying key")?;
+        let public_pem = Pem::new("PUBLIC KEY", public_der.as_bytes());
+
+        Ok(Self {
+            encoding_key,
+            public_pem,
+        })
+    }
+
+    /// The public key that SNAPs use to verify the issued tokens
+    pub fn public_pem(&self) -> Pem {
+        self.public_pem.clone()
+    }
+
+    /// Issue a token for a new PSSID, which is valid for any SNAP of the simulator
+    pub fn issue(&self) -> anyhow::Result<String> {
+        let exp = (SystemTime::now() + TOKEN_VALIDITY)
+            .duration_since(UNIX_EPOCH)
+            .context("System time is before the Unix epoch")?
+            .as_secs();
+        let claims = SnapTokenClaims {
+            pssid: Pssid::new(),
+            exp,
+        };
+
+        jsonwebtoken::encode(&Header::new(Algorithm::EdDSA), &claims, &self.encoding_key)
+            .context("Failed to sign SNAP token")
+    }
+}
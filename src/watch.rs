@@ -0,0 +1,52 @@
+//! Watching the config files for changes, by polling their modification times
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// How often the files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a set of files for changes
+pub struct ConfigWatcher {
+    files: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+}
+
+impl ConfigWatcher {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let modified = modification_times(&files);
+        Self { files, modified }
+    }
+
+    /// Wait until a file has changed. The files must then be left alone for one poll interval
+    /// before this returns, so a file that is still being written is not read half-way.
+    pub async fn changed(&mut self) {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let mut current = modification_times(&self.files);
+            if current == self.modified {
+                continue;
+            }
+
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let settled = modification_times(&self.files);
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+            self.modified = current;
+            return;
+        }
+    }
+}
+
+/// The modification time of every file, or `None` for files that can not be read, for example
+/// while an editor replaces them
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}